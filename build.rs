@@ -1,6 +1,12 @@
 use std::{env, fs, path::PathBuf};
 
 fn main() {
+    // Only the firmware target needs the memory layout and linker script;
+    // host builds (`cargo test-host`) link against std as usual.
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("none") {
+        return;
+    }
+
     // Locate OUT_DIR
     let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
 
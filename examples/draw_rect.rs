@@ -14,9 +14,13 @@ use embassy_rp::gpio::{Input, Level, Output, Pull};
 use embassy_rp::spi::{Config as SpiConfig, Phase, Polarity, Spi};
 use panic_probe as _;
 
-use pico_epd_driver::epd_driver::{Epd800x480, EpdBus, HEIGHT, WIDTH};
+use pico_epd_driver::epd_driver::{Epd, EpdBus, Panel7in5V2, PanelSpec};
 use pico_epd_driver::ui::pack_bitmap;
 
+// Swap in e.g. `Panel5in83V2` for the 5.83" panel.
+type Panel = Panel7in5V2;
+const WIDTH: usize = Panel::WIDTH;
+const HEIGHT: usize = Panel::HEIGHT;
 const RESOLUTION: usize = WIDTH * HEIGHT;
 
 #[global_allocator]
@@ -50,7 +54,7 @@ async fn main(_spawner: Spawner) {
 
     // Initialize EPD
     let bus = EpdBus::new(spi, cs, dc, rst, busy);
-    let mut epd = Epd800x480::new(bus, led);
+    let mut epd: Epd<Panel, _, _, _, _, _, _> = Epd::new(bus, led);
     if epd.init().await.is_err() {
         panic!();
     }
@@ -0,0 +1,381 @@
+//! Host-side LUT inspector and converter.
+//!
+//! ```text
+//! cargo lut-tool show fast@cold --pll 06
+//! cargo lut-tool ascii my.lut --scale 2
+//! cargo lut-tool svg my.lut -o my.svg
+//! cargo lut-tool rust my.lut --name FAST_TEXT_HC > src/epd_driver/luts/mine.rs
+//! ```
+//!
+//! A LUT is either a file in the text or binary format of
+//! `epd_driver::luts::format`, or a built-in table named `official`, `fast`,
+//! `terminal` or `gray4`, optionally followed by `@cold` or `@freezing`.
+
+use std::fmt::Write as _;
+use std::process::ExitCode;
+use std::{env, fs};
+
+use pico_epd_driver::epd_driver::{
+    DisplayMode, FrameRate, Level, LutSet, PhaseGroup, TempRange, phase_groups,
+};
+
+const USAGE: &str = "\
+usage: lut-tool <command> <lut> [options]
+
+commands:
+  show   timing table, DC balance and refresh time   [--pll HEX]
+  ascii  waveform diagram per transition             [--scale FRAMES]
+  svg    waveform diagram as SVG                     [-o FILE]
+  rust   Rust source for the `luts` module           [--name SUFFIX]
+  text   convert to the text format
+  bin    convert to the binary format                 -o FILE
+
+<lut> is a text or binary LUT file, or official|fast|terminal|gray4
+with an optional @cold or @freezing suffix.";
+
+/// `Pll` value of the panels in this crate.
+const DEFAULT_PLL: u8 = FrameRate::Hz50.pll();
+
+/// Widest ASCII diagram before `ascii` picks a coarser scale.
+const ASCII_COLUMNS: u32 = 100;
+
+/// The four LUTs a pixel is driven by, plus VCOM.
+const TABLES: [(&str, &str); 5] = [
+    ("vcom", "VCOM"),
+    ("ww", "white -> white"),
+    ("bw", "black -> white"),
+    ("wb", "white -> black"),
+    ("bb", "black -> black"),
+];
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("lut-tool: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let [cmd, lut, opts @ ..] = args else {
+        return Err(USAGE.into());
+    };
+    let luts = load(lut)?;
+    let opt = |name: &str| {
+        opts.iter()
+            .position(|o| o == name)
+            .and_then(|i| opts.get(i + 1))
+            .map(String::as_str)
+    };
+
+    match cmd.as_str() {
+        "show" => {
+            let pll = match opt("--pll") {
+                Some(v) => u8::from_str_radix(v.trim_start_matches("0x"), 16)
+                    .map_err(|_| format!("bad --pll value `{v}`"))?,
+                None => DEFAULT_PLL,
+            };
+            print!("{}", show(&luts, pll));
+        }
+        "ascii" => {
+            let scale = match opt("--scale") {
+                Some(v) => v
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|&s| s > 0)
+                    .ok_or(format!("bad --scale value `{v}`"))?,
+                None => luts.duration_frames().div_ceil(ASCII_COLUMNS).max(1),
+            };
+            print!("{}", ascii(&luts, scale));
+        }
+        "svg" => output(opt("-o"), svg(&luts).as_bytes())?,
+        "rust" => print!("{}", rust(&luts, opt("--name"))),
+        "text" => print!("{luts}"),
+        "bin" => {
+            let path = opt("-o").ok_or("`bin` needs -o FILE")?;
+            output(Some(path), &luts.to_bytes())?;
+        }
+        _ => return Err(USAGE.into()),
+    }
+    Ok(())
+}
+
+fn load(spec: &str) -> Result<LutSet, String> {
+    let (name, range) = spec.split_once('@').unwrap_or((spec, ""));
+    let mode = match name {
+        "official" => Some(DisplayMode::Official),
+        "fast" => Some(DisplayMode::Fast),
+        "terminal" => Some(DisplayMode::Terminal),
+        "gray4" => Some(DisplayMode::Gray4),
+        _ => None,
+    };
+    if let Some(mode) = mode {
+        let range = match range {
+            "" => TempRange::Normal,
+            "cold" => TempRange::Cold,
+            "freezing" => TempRange::Freezing,
+            r => return Err(format!("unknown temperature range `{r}`")),
+        };
+        return Ok(mode.lut_set_for(range));
+    }
+
+    let bytes = fs::read(spec).map_err(|e| format!("{spec}: {e}"))?;
+    let parsed = if bytes.starts_with(b"LUT") && bytes.len() == LutSet::BIN_LEN {
+        LutSet::from_bytes(&bytes)
+    } else {
+        let text = String::from_utf8(bytes).map_err(|_| format!("{spec}: not a LUT file"))?;
+        LutSet::from_text(&text)
+    };
+    parsed.map_err(|e| format!("{spec}: {e:?}"))
+}
+
+fn output(path: Option<&str>, data: &[u8]) -> Result<(), String> {
+    match path {
+        Some(p) => fs::write(p, data).map_err(|e| format!("{p}: {e}")),
+        None => {
+            print!("{}", String::from_utf8_lossy(data));
+            Ok(())
+        }
+    }
+}
+
+fn tables(luts: &LutSet) -> [&[u8; 42]; 5] {
+    [&luts.vcom, &luts.ww, &luts.bw, &luts.wb, &luts.bb]
+}
+
+fn level_name(l: Level, table: &str) -> &'static str {
+    match (l, table) {
+        (Level::Gnd, "vcom") => "DC",
+        (Level::Vdh, "vcom") => "VCOMH",
+        (Level::Vdl, "vcom") => "VCOML",
+        (Level::Vdhr, "vcom") => "float",
+        (Level::Gnd, _) => "GND",
+        (Level::Vdh, _) => "VDH",
+        (Level::Vdl, _) => "VDL",
+        (Level::Vdhr, _) => "VDHR",
+    }
+}
+
+/// Groups that contribute to the waveform.
+fn active(groups: &[PhaseGroup; 7]) -> impl Iterator<Item = (usize, &PhaseGroup)> {
+    groups
+        .iter()
+        .enumerate()
+        .filter(|(_, g)| g.duration_frames() > 0)
+}
+
+fn show(luts: &LutSet, pll: u8) -> String {
+    let mut out = String::new();
+    let vf: Vec<String> = luts
+        .voltage_frame
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect();
+    let _ = writeln!(out, "voltage_frame: {}\n", vf.join(" "));
+
+    for ((name, desc), lut) in TABLES.iter().zip(tables(luts)) {
+        let _ = writeln!(out, "{name} ({desc})");
+        let _ = writeln!(
+            out,
+            "  grp  phase 0     phase 1     phase 2     phase 3     rep  frames"
+        );
+        let groups = phase_groups(lut);
+        for (i, g) in active(&groups) {
+            let _ = write!(out, "  {i:<3}");
+            for (l, f) in g.levels.iter().zip(g.frames) {
+                let _ = write!(out, "  {:<5}x{f:<4}", level_name(*l, name));
+            }
+            let _ = writeln!(out, "  {:>3}  {:>6}", g.repeat, g.duration_frames());
+        }
+        let total: u32 = groups.iter().map(|g| g.duration_frames()).sum();
+        let _ = writeln!(out, "  total {total} frames\n");
+    }
+
+    let offsets = luts.dc_offsets();
+    let _ = writeln!(
+        out,
+        "DC offset (frames vs VCOM): ww {} bw {} wb {} bb {}",
+        offsets[0], offsets[1], offsets[2], offsets[3]
+    );
+    let _ = match luts.validate() {
+        Ok(()) => writeln!(out, "validate: ok"),
+        Err(e) => writeln!(out, "validate: {e:?}"),
+    };
+    let _ = match (luts.estimated_refresh_time(pll), FrameRate::from_pll(pll)) {
+        (Some(t), Some(rate)) => writeln!(
+            out,
+            "refresh: {} frames at {} Hz = {} ms",
+            luts.duration_frames(),
+            rate.hz(),
+            t.as_millis()
+        ),
+        _ => writeln!(out, "refresh: PLL value {pll:02X} is reserved"),
+    };
+    out
+}
+
+/// The level of every frame, repeats unrolled.
+fn frames(lut: &[u8; 42]) -> Vec<Level> {
+    let mut out = Vec::new();
+    for g in phase_groups(lut) {
+        for _ in 0..g.repeat {
+            for (l, f) in g.levels.iter().zip(g.frames) {
+                out.extend(std::iter::repeat_n(*l, f as usize));
+            }
+        }
+    }
+    out
+}
+
+/// Frame index at which each active group starts.
+fn group_starts(lut: &[u8; 42]) -> Vec<(usize, u32)> {
+    let mut at = 0;
+    let mut out = Vec::new();
+    for (i, g) in phase_groups(lut).iter().enumerate() {
+        if g.duration_frames() > 0 {
+            out.push((i, at));
+        }
+        at += g.duration_frames();
+    }
+    out
+}
+
+fn ascii(luts: &LutSet, scale: u32) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "one column = {scale} frame(s)\n");
+    for ((name, desc), lut) in TABLES.iter().zip(tables(luts)) {
+        let levels = frames(lut);
+        let cols = (levels.len() as u32).div_ceil(scale) as usize;
+        let at = |c: usize| levels[c * scale as usize];
+
+        let mut ruler = vec![' '; cols];
+        for (i, start) in group_starts(lut) {
+            ruler[(start / scale) as usize] = char::from(b'0' + i as u8);
+        }
+        let _ = writeln!(out, "{name} ({desc})");
+        let _ = writeln!(out, "  grp  |{}|", ruler.iter().collect::<String>());
+
+        let mut rows = vec![Level::Vdh, Level::Gnd, Level::Vdl];
+        if levels.contains(&Level::Vdhr) {
+            rows.insert(0, Level::Vdhr);
+        }
+        for row in rows {
+            let line: String = (0..cols)
+                .map(|c| if at(c) == row { '#' } else { ' ' })
+                .collect();
+            let _ = writeln!(out, "  {:<5}|{line}|", level_name(row, name));
+        }
+        let _ = writeln!(out);
+    }
+    out
+}
+
+fn svg(luts: &LutSet) -> String {
+    const PX: u32 = 4;
+    const LANE: u32 = 70;
+    const LEFT: u32 = 140;
+    let width = LEFT + luts.duration_frames().max(1) * PX + 20;
+    let height = LANE * TABLES.len() as u32 + 20;
+    let y_of = |l: Level| match l {
+        Level::Vdhr => 10,
+        Level::Vdh => 20,
+        Level::Gnd => 35,
+        Level::Vdl => 50,
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" font-family="monospace" font-size="12">"#
+    );
+    let _ = writeln!(
+        out,
+        r#"<rect width="{width}" height="{height}" fill="white"/>"#
+    );
+    for (lane, ((name, desc), lut)) in TABLES.iter().zip(tables(luts)).enumerate() {
+        let top = 10 + lane as u32 * LANE;
+        let _ = writeln!(out, r#"<g transform="translate(0,{top})">"#);
+        let _ = writeln!(out, r#"<text x="4" y="30">{name}</text>"#);
+        let _ = writeln!(
+            out,
+            r##"<text x="4" y="46" fill="#666">{}</text>"##,
+            desc.replace('>', "&gt;")
+        );
+        for level in [Level::Vdh, Level::Gnd, Level::Vdl] {
+            let y = y_of(level);
+            let _ = writeln!(
+                out,
+                r##"<line x1="{LEFT}" y1="{y}" x2="{}" y2="{y}" stroke="#ddd"/><text x="{}" y="{}" fill="#999" font-size="9">{}</text>"##,
+                width - 20,
+                LEFT - 30,
+                y + 3,
+                level_name(level, name)
+            );
+        }
+        for (i, start) in group_starts(lut) {
+            let x = LEFT + start * PX;
+            let _ = writeln!(
+                out,
+                r##"<line x1="{x}" y1="5" x2="{x}" y2="60" stroke="#aac"/><text x="{}" y="12" fill="#66a" font-size="9">{i}</text>"##,
+                x + 2
+            );
+        }
+
+        let mut points = String::new();
+        let mut x = LEFT;
+        for level in frames(lut) {
+            let y = y_of(level);
+            let _ = write!(points, "{x},{y} {},{y} ", x + PX);
+            x += PX;
+        }
+        let _ = writeln!(
+            out,
+            r#"<polyline points="{}" fill="none" stroke="black" stroke-width="1.5"/>"#,
+            points.trim_end()
+        );
+        let _ = writeln!(out, "</g>");
+    }
+    let _ = writeln!(out, "</svg>");
+    out
+}
+
+fn rust(luts: &LutSet, name: Option<&str>) -> String {
+    let suffix = name.map(|n| format!("_{n}")).unwrap_or_default();
+    let array = |bytes: &[u8], per_line: usize| {
+        let lines: Vec<String> = bytes
+            .chunks(per_line)
+            .map(|c| {
+                let items: Vec<String> = c.iter().map(|b| format!("0x{b:X},")).collect();
+                format!("    {}", items.join(" "))
+            })
+            .collect();
+        lines.join("\n")
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(out, "use super::{{LutSet, table}};\n");
+    let _ = writeln!(
+        out,
+        "const VOLTAGE_FRAME{suffix}: [u8; 7] = [\n{}\n];\n",
+        array(&luts.voltage_frame, 7)
+    );
+    for ((name, _), lut) in TABLES.iter().zip(tables(luts)) {
+        let _ = writeln!(
+            out,
+            "const LUT_{}{suffix}: [u8; 42] = [\n{}\n];",
+            name.to_uppercase(),
+            array(lut, 6)
+        );
+    }
+    let _ = writeln!(out, "\npub const LUTS: LutSet = LutSet {{");
+    let _ = writeln!(out, "    voltage_frame: VOLTAGE_FRAME{suffix},");
+    for (name, _) in TABLES {
+        let _ = writeln!(out, "    {name}: LUT_{}{suffix},", name.to_uppercase());
+    }
+    let _ = writeln!(out, "}};\n");
+    let _ = writeln!(out, "pub const TABLE: [LutSet; 3] = table(LUTS);");
+    out
+}
@@ -3,6 +3,7 @@
 #![allow(clippy::needless_range_loop)]
 
 use core::convert::Infallible;
+use core::marker::PhantomData;
 use embassy_time::Instant;
 use embedded_graphics::{
     draw_target::DrawTarget,
@@ -16,15 +17,22 @@ use embedded_graphics::{
 use heapless::{String as HString, Vec as HVec};
 use static_cell::StaticCell;
 
-use crate::epd_driver::{BUF_SIZE, HEIGHT, WIDTH};
+use crate::epd_driver::{BUF_SIZE, HEIGHT, Panel7in5V2, PanelSpec, Rect};
+use crate::orientation::{self, Orientation};
 
 // ---------- Console config ----------
 pub const MARGIN: i32 = 9;
 pub const LINE_H: i32 = 18; // FONT_9X18
-pub const MAX_VISIBLE_LINES: usize = ((HEIGHT as i32 - 2 * MARGIN) / LINE_H) as usize;
+/// Visible lines on the default 800x480 panel.
+pub const MAX_VISIBLE_LINES: usize = max_visible_lines(HEIGHT);
 pub const HISTORY_CAP: usize = 128;
 pub const LINE_CAP: usize = 96;
 
+/// Lines that fit on a panel `height` pixels tall.
+pub const fn max_visible_lines(height: usize) -> usize {
+    ((height as i32 - 2 * MARGIN) / LINE_H) as usize
+}
+
 // Dedicated console framebuffer
 static CONSOLE_FB: StaticCell<[u8; BUF_SIZE]> = StaticCell::new();
 
@@ -33,19 +41,39 @@ pub struct MonoBuf<'a> {
     buf: &'a mut [u8],
     w: u32,
     h: u32,
+    orientation: Orientation,
 }
 
 impl<'a> MonoBuf<'a> {
+    /// `w` and `h` are the panel's native size.
     pub fn new(buf: &'a mut [u8], w: u32, h: u32) -> Self {
-        Self { buf, w, h }
+        Self {
+            buf,
+            w,
+            h,
+            orientation: Orientation::default(),
+        }
+    }
+
+    /// Rotate or mirror what is drawn from now on.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.orientation = orientation;
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
     }
 
     #[inline]
     fn set_pixel(&mut self, x: u32, y: u32, on: bool) {
-        if x >= self.w || y >= self.h {
+        let (w, h) = self.orientation.size(self.w as usize, self.h as usize);
+        if x as usize >= w || y as usize >= h {
             return;
         }
-        let idx = (y * self.w + x) as usize;
+        let (x, y) =
+            self.orientation
+                .to_panel(x as usize, y as usize, self.w as usize, self.h as usize);
+        let idx = y * self.w as usize + x;
         let byte = idx >> 3;
         let bit = 7 - (idx & 7);
         if on {
@@ -68,7 +96,8 @@ impl<'a> MonoBuf<'a> {
 
 impl OriginDimensions for MonoBuf<'_> {
     fn size(&self) -> Size {
-        Size::new(self.w, self.h)
+        let (w, h) = self.orientation.size(self.w as usize, self.h as usize);
+        Size::new(w as u32, h as u32)
     }
 }
 
@@ -106,20 +135,33 @@ pub enum RefreshStrategy {
 }
 
 /// Console buffer state - manages history and framebuffer without display logic
-pub struct ConsoleBuffer<'a> {
+pub struct ConsoleBuffer<'a, P = Panel7in5V2> {
     draw: MonoBuf<'a>,
     style: MonoTextStyle<'static, BinaryColor>,
     show_border: bool,
     history: HVec<HString<LINE_CAP>, HISTORY_CAP>,
     last_line_count: usize,
     new_lines_since_render: usize,
+    panel: PhantomData<P>,
 }
 
 impl<'a> ConsoleBuffer<'a> {
     /// Create a new console buffer with its own framebuffer
     pub fn new() -> Self {
         let fb: &mut [u8; BUF_SIZE] = CONSOLE_FB.init([0; BUF_SIZE]);
-        let mut draw = MonoBuf::new(&mut fb[..], WIDTH as u32, HEIGHT as u32);
+        Self::with_buffer(&mut fb[..])
+    }
+}
+
+impl<'a, P: PanelSpec> ConsoleBuffer<'a, P> {
+    /// Create a console buffer drawing into `buf` (`P::BUF_SIZE` bytes)
+    pub fn with_buffer(buf: &'a mut [u8]) -> Self {
+        assert_eq!(
+            buf.len(),
+            P::BUF_SIZE,
+            "console buffer must be BUF_SIZE bytes"
+        );
+        let mut draw = MonoBuf::new(buf, P::WIDTH as u32, P::HEIGHT as u32);
         draw.clear(Off).ok();
         let style = MonoTextStyle::new(&FONT_9X18, On);
 
@@ -130,6 +172,7 @@ impl<'a> ConsoleBuffer<'a> {
             history: HVec::new(),
             last_line_count: 0,
             new_lines_since_render: 0,
+            panel: PhantomData,
         }
     }
 
@@ -138,6 +181,23 @@ impl<'a> ConsoleBuffer<'a> {
         self.show_border = on;
     }
 
+    /// Rotate or mirror the console, e.g. [`Rotation::Deg90`] for a portrait
+    /// layout. Takes effect at the next render.
+    ///
+    /// [`Rotation::Deg90`]: crate::orientation::Rotation::Deg90
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.draw.set_orientation(orientation);
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.draw.orientation()
+    }
+
+    /// Console size in drawing coordinates.
+    fn logical_size(&self) -> (usize, usize) {
+        self.draw.orientation().size(P::WIDTH, P::HEIGHT)
+    }
+
     /// Clear all history
     pub fn clear_history(&mut self) {
         self.history.clear();
@@ -183,10 +243,11 @@ impl<'a> ConsoleBuffer<'a> {
     /// This updates the internal framebuffer but does NOT trigger any display updates
     pub fn render(&mut self) {
         self.draw.clear(Off).ok();
+        let (_, height) = self.logical_size();
 
         // Draw border if enabled
         if self.show_border {
-            Rectangle::new(Point::new(0, 0), Size::new(WIDTH as u32, HEIGHT as u32))
+            Rectangle::new(Point::new(0, 0), self.draw.size())
                 .into_styled(PrimitiveStyle::with_stroke(On, 1))
                 .draw(&mut self.draw)
                 .ok();
@@ -194,7 +255,7 @@ impl<'a> ConsoleBuffer<'a> {
 
         // Draw visible lines
         let total = self.history.len();
-        let start = total.saturating_sub(MAX_VISIBLE_LINES);
+        let start = total.saturating_sub(max_visible_lines(height));
         let visible = &self.history[start..total];
 
         let mut y = MARGIN;
@@ -208,7 +269,7 @@ impl<'a> ConsoleBuffer<'a> {
             .draw(&mut self.draw)
             .ok();
             y += LINE_H;
-            if (y + LINE_H + MARGIN) >= HEIGHT as i32 {
+            if (y + LINE_H + MARGIN) >= height as i32 {
                 break;
             }
         }
@@ -224,7 +285,9 @@ impl<'a> ConsoleBuffer<'a> {
 
     /// Get the number of lines currently visible
     pub fn visible_line_count(&self) -> usize {
-        self.history.len().min(MAX_VISIBLE_LINES)
+        self.history
+            .len()
+            .min(max_visible_lines(self.logical_size().1))
     }
 
     /// Get the number of new lines since last render
@@ -244,7 +307,31 @@ impl<'a> ConsoleBuffer<'a> {
         lines_to_update as i32 * LINE_H
     }
 
-    /// Extract buffer data for a specific rectangle
+    /// Rectangle spanning the console width over the newest
+    /// `lines_to_update` lines, in drawing coordinates.
+    pub fn partial_update_rect(&self, lines_to_update: usize) -> Rect {
+        Rect {
+            x: 0,
+            y: self.partial_update_y_start(lines_to_update) as usize,
+            w: self.logical_size().0,
+            h: self.partial_update_height(lines_to_update) as usize,
+        }
+    }
+
+    /// The panel rectangle covering `r` (in drawing coordinates), widened to
+    /// the panel's `X_ALIGN` so it can be passed to [`extract_rect_data`]
+    /// and `display_partial`.
+    ///
+    /// [`extract_rect_data`]: Self::extract_rect_data
+    pub fn panel_rect(&self, r: Rect) -> Rect {
+        let r = self
+            .draw
+            .orientation()
+            .rect_to_panel(r, P::WIDTH, P::HEIGHT);
+        orientation::align_x(r, P::X_ALIGN)
+    }
+
+    /// Extract buffer data for a specific rectangle in panel coordinates
     pub fn extract_rect_data(&self, x: usize, y: usize, w: usize, h: usize) -> HVec<u8, 4096> {
         let mut partial_buf = HVec::new();
 
@@ -252,13 +339,13 @@ impl<'a> ConsoleBuffer<'a> {
             return partial_buf;
         }
 
-        let bytes_per_full_row = WIDTH / 8;
+        let bytes_per_full_row = P::WIDTH / 8;
         let bytes_per_partial_row = w / 8;
 
         // Extract bytes row by row
         for dy in 0..h {
             let actual_y = y + dy;
-            if actual_y >= HEIGHT {
+            if actual_y >= P::HEIGHT {
                 break;
             }
 
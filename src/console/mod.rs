@@ -1,48 +1,56 @@
 pub mod buffer;
 pub mod ui;
 
-use embedded_hal::digital::{ErrorType as DigitalErrorType, InputPin, OutputPin};
-use embedded_hal::spi::ErrorType as SpiErrorType;
-use embedded_hal_async::{digital::Wait, spi::SpiBus};
-
-use crate::epd_driver::{DriverError, Epd800x480};
+use crate::epd_driver::{EpdDriver, Panel7in5V2};
+use crate::orientation::Orientation;
+use buffer::ConsoleBuffer;
 use ui::ConsoleUI;
 
 /// Simplified EPD console with automatic refresh decisions.
 ///
 /// This is a backwards-compatible wrapper around ConsoleUI.
-pub struct EpdConsole<'a, SPI, CS, DC, RST, BUSY, LED> {
-    ui: ConsoleUI<'a>,
-    epd: &'a mut Epd800x480<SPI, CS, DC, RST, BUSY, LED>,
+pub struct EpdConsole<'a, D: EpdDriver> {
+    ui: ConsoleUI<'a, D::Panel>,
+    epd: &'a mut D,
 }
 
-impl<'a, SPI, CS, DC, RST, BUSY, LED> EpdConsole<'a, SPI, CS, DC, RST, BUSY, LED>
+impl<'a, D> EpdConsole<'a, D>
 where
-    SPI: SpiBus<u8> + SpiErrorType,
-    CS: OutputPin + DigitalErrorType,
-    DC: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-    RST: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-    BUSY: InputPin + Wait + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-    LED: OutputPin,
+    D: EpdDriver<Panel = Panel7in5V2>,
 {
     /// Create a console with its own framebuffer.
-    pub fn new(epd: &'a mut Epd800x480<SPI, CS, DC, RST, BUSY, LED>) -> Self {
+    pub fn new(epd: &'a mut D) -> Self {
         Self {
             ui: ConsoleUI::new(),
             epd,
         }
     }
+}
+
+impl<'a, D: EpdDriver> EpdConsole<'a, D> {
+    /// Create a console drawing into `buf` (`BUF_SIZE` bytes of the panel).
+    pub fn with_buffer(epd: &'a mut D, buf: &'a mut [u8]) -> Self {
+        Self {
+            ui: ConsoleUI::with_buffer(ConsoleBuffer::with_buffer(buf)),
+            epd,
+        }
+    }
 
     /// Push a line into history and automatically refresh the display.
-    pub async fn push(&mut self, s: &str) -> Result<(), DriverError<SPI, CS>> {
+    pub async fn push(&mut self, s: &str) -> Result<(), D::Error> {
         self.ui.push(s, self.epd).await
     }
 
     /// Show the console (makes it visible and refreshes)
-    pub async fn show(&mut self) -> Result<(), DriverError<SPI, CS>> {
+    pub async fn show(&mut self) -> Result<(), D::Error> {
         self.ui.show(self.epd).await
     }
 
+    /// Rotate or mirror the console, e.g. to run it in portrait.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.ui.set_orientation(orientation);
+    }
+
     /// Hide the console
     pub fn hide(&mut self) {
         self.ui.hide();
@@ -5,35 +5,40 @@
 //! refresh the display based on buffer state.
 
 use embassy_time::{Duration, Timer};
-use embedded_hal::digital::{ErrorType as DigitalErrorType, InputPin, OutputPin};
-use embedded_hal::spi::ErrorType as SpiErrorType;
-use embedded_hal_async::{digital::Wait, spi::SpiBus};
 
 use super::buffer::{ConsoleBuffer, RefreshStrategy};
-use crate::epd_driver::{DriverError, Epd800x480, Rect, WIDTH};
+use crate::epd_driver::{EpdDriver, Panel7in5V2, PanelSpec};
+use crate::orientation::Orientation;
 
 /// Console UI controller - manages display updates
-pub struct ConsoleUI<'a> {
-    buffer: ConsoleBuffer<'a>,
+pub struct ConsoleUI<'a, P = Panel7in5V2> {
+    buffer: ConsoleBuffer<'a, P>,
     visible: bool,
 }
 
 impl<'a> ConsoleUI<'a> {
     /// Create a new console UI
     pub fn new() -> Self {
+        Self::with_buffer(ConsoleBuffer::new())
+    }
+}
+
+impl<'a, P: PanelSpec> ConsoleUI<'a, P> {
+    /// Create a console UI around an existing buffer
+    pub fn with_buffer(buffer: ConsoleBuffer<'a, P>) -> Self {
         Self {
-            buffer: ConsoleBuffer::new(),
+            buffer,
             visible: false,
         }
     }
 
     /// Get mutable reference to the buffer for configuration
-    pub fn buffer_mut(&mut self) -> &mut ConsoleBuffer<'a> {
+    pub fn buffer_mut(&mut self) -> &mut ConsoleBuffer<'a, P> {
         &mut self.buffer
     }
 
     /// Get reference to the buffer for reading
-    pub fn buffer(&self) -> &ConsoleBuffer<'a> {
+    pub fn buffer(&self) -> &ConsoleBuffer<'a, P> {
         &self.buffer
     }
 
@@ -42,23 +47,20 @@ impl<'a> ConsoleUI<'a> {
         self.buffer.set_border(on);
     }
 
+    /// Rotate or mirror the console; shown at the next refresh
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.buffer.set_orientation(orientation);
+    }
+
     /// Clear history and reset state
     pub fn clear_history(&mut self) {
         self.buffer.clear_history();
     }
 
     /// Show the console
-    pub async fn show<SPI, CS, DC, RST, BUSY, LED>(
-        &mut self,
-        epd: &mut Epd800x480<SPI, CS, DC, RST, BUSY, LED>,
-    ) -> Result<(), DriverError<SPI, CS>>
+    pub async fn show<D>(&mut self, epd: &mut D) -> Result<(), D::Error>
     where
-        SPI: SpiBus<u8> + SpiErrorType,
-        CS: OutputPin + DigitalErrorType,
-        DC: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        RST: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        BUSY: InputPin + Wait + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        LED: OutputPin,
+        D: EpdDriver<Panel = P>,
     {
         self.visible = true;
         self.buffer.render();
@@ -78,18 +80,9 @@ impl<'a> ConsoleUI<'a> {
     }
 
     /// Push a line and automatically update display if visible
-    pub async fn push<SPI, CS, DC, RST, BUSY, LED>(
-        &mut self,
-        msg: &str,
-        epd: &mut Epd800x480<SPI, CS, DC, RST, BUSY, LED>,
-    ) -> Result<(), DriverError<SPI, CS>>
+    pub async fn push<D>(&mut self, msg: &str, epd: &mut D) -> Result<(), D::Error>
     where
-        SPI: SpiBus<u8> + SpiErrorType,
-        CS: OutputPin + DigitalErrorType,
-        DC: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        RST: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        BUSY: InputPin + Wait + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        LED: OutputPin,
+        D: EpdDriver<Panel = P>,
     {
         let strategy = self.buffer.push_line(msg);
 
@@ -101,18 +94,13 @@ impl<'a> ConsoleUI<'a> {
     }
 
     /// Refresh display based on strategy
-    async fn refresh_display<SPI, CS, DC, RST, BUSY, LED>(
+    async fn refresh_display<D>(
         &mut self,
-        epd: &mut Epd800x480<SPI, CS, DC, RST, BUSY, LED>,
+        epd: &mut D,
         strategy: RefreshStrategy,
-    ) -> Result<(), DriverError<SPI, CS>>
+    ) -> Result<(), D::Error>
     where
-        SPI: SpiBus<u8> + SpiErrorType,
-        CS: OutputPin + DigitalErrorType,
-        DC: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        RST: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        BUSY: InputPin + Wait + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        LED: OutputPin,
+        D: EpdDriver<Panel = P>,
     {
         self.buffer.render();
 
@@ -134,18 +122,9 @@ impl<'a> ConsoleUI<'a> {
     }
 
     /// Perform partial display update
-    async fn partial_update<SPI, CS, DC, RST, BUSY, LED>(
-        &mut self,
-        epd: &mut Epd800x480<SPI, CS, DC, RST, BUSY, LED>,
-        new_lines: usize,
-    ) -> Result<(), DriverError<SPI, CS>>
+    async fn partial_update<D>(&mut self, epd: &mut D, new_lines: usize) -> Result<(), D::Error>
     where
-        SPI: SpiBus<u8> + SpiErrorType,
-        CS: OutputPin + DigitalErrorType,
-        DC: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        RST: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        BUSY: InputPin + Wait + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        LED: OutputPin,
+        D: EpdDriver<Panel = P>,
     {
         if new_lines == 0 {
             return Ok(());
@@ -160,15 +139,9 @@ impl<'a> ConsoleUI<'a> {
         }
 
         let lines_to_update = new_lines.min(current_visible);
-        let y_start = self.buffer.partial_update_y_start(lines_to_update);
-        let y_height = self.buffer.partial_update_height(lines_to_update);
-
-        let rect = Rect {
-            x: 0,
-            y: y_start as usize,
-            w: WIDTH,
-            h: y_height as usize,
-        };
+        let rect = self
+            .buffer
+            .panel_rect(self.buffer.partial_update_rect(lines_to_update));
 
         let partial_buf = self
             .buffer
@@ -182,18 +155,9 @@ impl<'a> ConsoleUI<'a> {
     }
 
     /// Log an info message
-    pub async fn log_info<SPI, CS, DC, RST, BUSY, LED>(
-        &mut self,
-        msg: &str,
-        epd: &mut Epd800x480<SPI, CS, DC, RST, BUSY, LED>,
-    ) -> Result<(), DriverError<SPI, CS>>
+    pub async fn log_info<D>(&mut self, msg: &str, epd: &mut D) -> Result<(), D::Error>
     where
-        SPI: SpiBus<u8> + SpiErrorType,
-        CS: OutputPin + DigitalErrorType,
-        DC: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        RST: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        BUSY: InputPin + Wait + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        LED: OutputPin,
+        D: EpdDriver<Panel = P>,
     {
         let mut full_msg: heapless::String<96> = heapless::String::new();
         let _ = full_msg.push_str("INFO: ");
@@ -202,18 +166,9 @@ impl<'a> ConsoleUI<'a> {
     }
 
     /// Log a warning message
-    pub async fn log_warn<SPI, CS, DC, RST, BUSY, LED>(
-        &mut self,
-        msg: &str,
-        epd: &mut Epd800x480<SPI, CS, DC, RST, BUSY, LED>,
-    ) -> Result<(), DriverError<SPI, CS>>
+    pub async fn log_warn<D>(&mut self, msg: &str, epd: &mut D) -> Result<(), D::Error>
     where
-        SPI: SpiBus<u8> + SpiErrorType,
-        CS: OutputPin + DigitalErrorType,
-        DC: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        RST: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        BUSY: InputPin + Wait + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        LED: OutputPin,
+        D: EpdDriver<Panel = P>,
     {
         let mut full_msg: heapless::String<96> = heapless::String::new();
         let _ = full_msg.push_str("WARN: ");
@@ -222,18 +177,9 @@ impl<'a> ConsoleUI<'a> {
     }
 
     /// Log an error message
-    pub async fn log_error<SPI, CS, DC, RST, BUSY, LED>(
-        &mut self,
-        msg: &str,
-        epd: &mut Epd800x480<SPI, CS, DC, RST, BUSY, LED>,
-    ) -> Result<(), DriverError<SPI, CS>>
+    pub async fn log_error<D>(&mut self, msg: &str, epd: &mut D) -> Result<(), D::Error>
     where
-        SPI: SpiBus<u8> + SpiErrorType,
-        CS: OutputPin + DigitalErrorType,
-        DC: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        RST: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        BUSY: InputPin + Wait + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-        LED: OutputPin,
+        D: EpdDriver<Panel = P>,
     {
         let mut full_msg: heapless::String<96> = heapless::String::new();
         let _ = full_msg.push_str("ERROR: ");
@@ -0,0 +1,143 @@
+//! A panel you can draw on directly.
+//!
+//! [`Display`] owns a driver and a [`Framebuffer`] for its panel, so any
+//! embedded-graphics primitive, font or image can be drawn on it and sent
+//! with [`flush`](Display::flush) or [`flush_partial`](Display::flush_partial).
+
+use core::convert::Infallible;
+use core::fmt;
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{OriginDimensions, Size},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::Rectangle,
+};
+
+use crate::epd_driver::{EpdDriver, PanelSpec, Rect};
+use crate::framebuffer::{Flush, Framebuffer};
+use crate::orientation::Orientation;
+
+/// An EPD driver together with a 1bpp framebuffer of its panel.
+///
+/// Drawing only changes the framebuffer; nothing reaches the panel until a
+/// flush.
+pub struct Display<'a, D: EpdDriver> {
+    epd: D,
+    fb: Framebuffer<'a, D::Panel>,
+}
+
+/// A buffer passed to [`Display::new`] had the wrong size. Holds the
+/// driver so it is not lost.
+pub struct BadBuffer<D> {
+    pub epd: D,
+    pub expected: usize,
+    pub got: usize,
+}
+
+// Drivers are not `Debug`, so `unwrap` works on any `BadBuffer`.
+impl<D> fmt::Debug for BadBuffer<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BadBuffer")
+            .field("expected", &self.expected)
+            .field("got", &self.got)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, D: EpdDriver> Display<'a, D> {
+    /// Wrap `epd`, drawing into `buf`, which must be `BUF_SIZE` bytes of
+    /// the driver's panel. Its current contents are assumed to be on screen
+    /// already.
+    pub fn new(epd: D, buf: &'a mut [u8]) -> Result<Self, BadBuffer<D>> {
+        if buf.len() != D::Panel::BUF_SIZE {
+            return Err(BadBuffer {
+                epd,
+                expected: D::Panel::BUF_SIZE,
+                got: buf.len(),
+            });
+        }
+        Ok(Self {
+            epd,
+            fb: Framebuffer::for_panel(buf),
+        })
+    }
+
+    pub fn epd(&self) -> &D {
+        &self.epd
+    }
+
+    /// The driver, e.g. to change its mode or put it to sleep.
+    pub fn epd_mut(&mut self) -> &mut D {
+        &mut self.epd
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer<'a, D::Panel> {
+        &self.fb
+    }
+
+    pub fn framebuffer_mut(&mut self) -> &mut Framebuffer<'a, D::Panel> {
+        &mut self.fb
+    }
+
+    /// Rotate or mirror what is drawn from now on.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.fb.set_orientation(orientation);
+    }
+
+    /// Give the driver back.
+    pub fn release(self) -> D {
+        self.epd
+    }
+
+    /// Send what changed since the last flush; see [`Framebuffer::flush`].
+    pub async fn flush(&mut self) -> Result<Flush, D::Error> {
+        self.fb.flush(&mut self.epd).await
+    }
+
+    /// Send the whole framebuffer with a full refresh.
+    pub async fn flush_full(&mut self) -> Result<(), D::Error> {
+        self.epd.display(self.fb.buffer()).await?;
+        self.fb.mark_clean();
+        Ok(())
+    }
+
+    /// Partially refresh just `area` (in drawing coordinates, clipped to the
+    /// screen), widened to the panel's `X_ALIGN`. Changes outside it stay
+    /// pending for the next [`flush`](Self::flush).
+    pub async fn flush_partial(&mut self, area: Rectangle) -> Result<(), D::Error> {
+        let area = area.intersection(&self.bounding_box());
+        if area.is_zero_sized() {
+            return Ok(());
+        }
+        let r = Rect {
+            x: area.top_left.x as usize,
+            y: area.top_left.y as usize,
+            w: area.size.width as usize,
+            h: area.size.height as usize,
+        };
+        self.fb.flush_rect(&mut self.epd, r).await
+    }
+}
+
+impl<D: EpdDriver> OriginDimensions for Display<'_, D> {
+    fn size(&self) -> Size {
+        self.fb.size()
+    }
+}
+
+impl<D: EpdDriver> DrawTarget for Display<'_, D> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.fb.draw_iter(pixels)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.fb.clear(color)
+    }
+}
@@ -0,0 +1,194 @@
+//! Blocking flavour of the drivers, for bare-metal and RTIC users.
+//!
+//! The async drivers are reused as they are, so both flavours send the same
+//! command sequences. [`BlockingSpi`] lets a blocking `embedded_hal` SPI bus
+//! stand in for the async one, [`PollingBusy`] polls the BUSY pin and times
+//! delays with a `DelayNs` provider, and [`Blocking`] runs each driver call to
+//! completion with [`block_on`]. Nothing in that stack ever returns
+//! `Pending`, so every future finishes on its first poll and neither an
+//! executor nor an Embassy time driver is needed.
+//!
+//! ```ignore
+//! let bus = EpdBus::new_blocking(spi, cs, dc, rst, busy, delay);
+//! let mut epd = Blocking::new(Epd800x480::new(bus, led));
+//! epd.init()?;
+//! epd.display(&frame)?;
+//! // Anything beyond the common calls goes through `block_on`.
+//! block_on(epd.inner_mut().set_mode(DisplayMode::Fast))?;
+//! ```
+
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+
+use embassy_time::{Duration, Instant};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{ErrorType as DigitalErrorType, InputPin};
+use embedded_hal::spi::{ErrorType as SpiErrorType, SpiBus};
+
+use super::Rect;
+use super::bus::{BusyLine, EpdBus};
+use super::traits::EpdDriver;
+
+/// Interval at which [`PollingBusy`] samples the BUSY pin.
+const POLL_MS: u32 = 1;
+
+/// Run a future to completion by polling it in a loop.
+///
+/// Meant for futures of drivers built on [`BlockingSpi`] and
+/// [`PollingBusy`], which are ready on the first poll. With async pins it
+/// still works, but spins instead of sleeping.
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+            return out;
+        }
+    }
+}
+
+/// A blocking SPI bus presented as an async one.
+pub struct BlockingSpi<S>(S);
+
+impl<S> BlockingSpi<S> {
+    pub fn new(spi: S) -> Self {
+        Self(spi)
+    }
+
+    pub fn release(self) -> S {
+        self.0
+    }
+}
+
+impl<S: SpiErrorType> SpiErrorType for BlockingSpi<S> {
+    type Error = S::Error;
+}
+
+impl<S: SpiBus<u8>> embedded_hal_async::spi::SpiBus<u8> for BlockingSpi<S> {
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.0.read(words)
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        self.0.write(words)
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        self.0.transfer(read, write)
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.0.transfer_in_place(words)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.flush()
+    }
+}
+
+/// A blocking BUSY pin, polled every millisecond and timed with `delay`.
+///
+/// It has no clock, so `RefreshPolicy::max_age` is not enforced.
+pub struct PollingBusy<B, D> {
+    pin: B,
+    delay: D,
+}
+
+impl<B, D> PollingBusy<B, D> {
+    pub fn new(pin: B, delay: D) -> Self {
+        Self { pin, delay }
+    }
+
+    pub fn release(self) -> (B, D) {
+        (self.pin, self.delay)
+    }
+}
+
+impl<B: DigitalErrorType, D> DigitalErrorType for PollingBusy<B, D> {
+    type Error = B::Error;
+}
+
+impl<B: InputPin, D: DelayNs> BusyLine for PollingBusy<B, D> {
+    async fn delay_ms(&mut self, ms: u32) {
+        self.delay.delay_ms(ms);
+    }
+
+    async fn wait_level(&mut self, high: bool, timeout: Duration) -> Result<bool, Self::Error> {
+        let mut waited = 0u64;
+        loop {
+            if self.pin.is_high()? == high {
+                return Ok(true);
+            }
+            if waited >= timeout.as_millis() {
+                return Ok(false);
+            }
+            self.delay.delay_ms(POLL_MS);
+            waited += POLL_MS as u64;
+        }
+    }
+
+    fn now(&self) -> Option<Instant> {
+        None
+    }
+}
+
+impl<SPI, CS, DC, RST, BUSY, D> EpdBus<BlockingSpi<SPI>, CS, DC, RST, PollingBusy<BUSY, D>> {
+    /// Build a bus from blocking HAL types and a `DelayNs` provider.
+    pub fn new_blocking(spi: SPI, cs: CS, dc: DC, rst: RST, busy: BUSY, delay: D) -> Self {
+        Self::new(
+            BlockingSpi::new(spi),
+            cs,
+            dc,
+            rst,
+            PollingBusy::new(busy, delay),
+        )
+    }
+}
+
+/// Blocking front-end for any driver.
+pub struct Blocking<D>(D);
+
+impl<D: EpdDriver> Blocking<D> {
+    pub fn new(driver: D) -> Self {
+        Self(driver)
+    }
+
+    pub fn inner(&self) -> &D {
+        &self.0
+    }
+
+    /// The async driver, for calls not mirrored here (wrap them in
+    /// [`block_on`]).
+    pub fn inner_mut(&mut self) -> &mut D {
+        &mut self.0
+    }
+
+    pub fn into_inner(self) -> D {
+        self.0
+    }
+
+    pub fn init(&mut self) -> Result<(), D::Error> {
+        block_on(self.0.init())
+    }
+
+    pub fn display(&mut self, buf: &[u8]) -> Result<(), D::Error> {
+        block_on(self.0.display(buf))
+    }
+
+    pub fn display_partial(&mut self, buf: &[u8], r: Rect) -> Result<(), D::Error> {
+        block_on(self.0.display_partial(buf, r))
+    }
+
+    pub fn display_region(&mut self, fb: &[u8], r: Rect) -> Result<(), D::Error> {
+        block_on(self.0.display_region(fb, r))
+    }
+
+    pub fn clear(&mut self) -> Result<(), D::Error> {
+        block_on(self.0.clear())
+    }
+
+    pub fn sleep(&mut self) -> Result<(), D::Error> {
+        block_on(self.0.sleep())
+    }
+}
@@ -1,15 +1,86 @@
-use embassy_time::{Duration, Timer};
+use core::convert::Infallible;
+
+use embassy_time::{Duration, Instant, Timer, with_timeout};
 use embedded_hal::digital::{ErrorType as DigitalErrorType, InputPin, OutputPin};
 use embedded_hal::spi::ErrorType as SpiErrorType;
 use embedded_hal_async::digital::Wait;
-use embedded_hal_async::spi::SpiBus;
+use embedded_hal_async::spi::{SpiBus, SpiDevice};
+
+use super::command::Command;
+use super::error::{DriverError, EpdDriverError};
+use super::timeout::{BusyOp, Timeouts};
+
+/// Bytes per SPI write when a driver generates data itself.
+pub(super) const CHUNK: usize = 256;
+
+/// The BUSY input together with the time source the drivers use for delays,
+/// bounded waits and refresh-age tracking.
+///
+/// Every async `InputPin + Wait` is a `BusyLine` timed by `embassy-time`;
+/// [`PollingBusy`](super::blocking::PollingBusy) provides one for blocking
+/// HALs without pulling in an Embassy time driver.
+#[allow(async_fn_in_trait)]
+pub trait BusyLine: DigitalErrorType {
+    async fn delay_ms(&mut self, ms: u32);
+
+    /// Wait until the line reads `high`. Returns `Ok(false)` if `timeout`
+    /// passed first.
+    async fn wait_level(&mut self, high: bool, timeout: Duration) -> Result<bool, Self::Error>;
+
+    /// Current time, if this line has a clock.
+    fn now(&self) -> Option<Instant>;
+}
+
+impl<T: InputPin + Wait> BusyLine for T {
+    async fn delay_ms(&mut self, ms: u32) {
+        Timer::after_millis(ms as u64).await;
+    }
+
+    async fn wait_level(&mut self, high: bool, timeout: Duration) -> Result<bool, Self::Error> {
+        let wait = async {
+            if high {
+                self.wait_for_high().await
+            } else {
+                self.wait_for_low().await
+            }
+        };
+        match with_timeout(timeout, wait).await {
+            Ok(r) => r.map(|_| true),
+            Err(_) => Ok(false),
+        }
+    }
+
+    fn now(&self) -> Option<Instant> {
+        Some(Instant::now())
+    }
+}
 
+/// A failed bus operation, tagged with the line that failed. Each pin keeps
+/// its own error type, so pins from different HALs or GPIO expanders can be
+/// mixed.
 #[derive(Debug)]
-pub enum EpdBusError<SpiE, GpioE> {
+pub enum EpdBusError<SpiE, CsE, DcE, RstE, BusyE> {
+    /// Writing to or reading from the SPI bus.
     Spi(SpiE),
-    Gpio(GpioE),
+    /// Selecting or releasing the chip.
+    Cs(CsE),
+    /// Switching between command and data.
+    Dc(DcE),
+    /// Driving the reset pulse.
+    Rst(RstE),
+    /// Reading or waiting on BUSY.
+    Busy(BusyE),
 }
 
+/// [`EpdBusError`] for the error types of a concrete set of lines.
+pub type BusError<SPI, CS, DC, RST, BUSY> = EpdBusError<
+    <SPI as SpiErrorType>::Error,
+    <CS as DigitalErrorType>::Error,
+    <DC as DigitalErrorType>::Error,
+    <RST as DigitalErrorType>::Error,
+    <BUSY as DigitalErrorType>::Error,
+>;
+
 pub struct EpdBus<SPI, CS, DC, RST, BUSY> {
     spi: SPI,
     cs: CS,
@@ -30,41 +101,53 @@ impl<SPI, CS, DC, RST, BUSY> EpdBus<SPI, CS, DC, RST, BUSY> {
     }
 }
 
+impl<DEV, DC, RST, BUSY> EpdBus<DeviceSpi<DEV>, NoCs, DC, RST, BUSY>
+where
+    DEV: SpiDevice<u8>,
+{
+    /// Build a bus over an `SpiDevice`, e.g. from `embedded-hal-bus` or
+    /// `embassy-embedded-hal`, so the panel can share its SPI bus with other
+    /// peripherals. The device drives CS: every command or data write is one
+    /// transaction, and DC is set before the transaction starts, so another
+    /// device's traffic never sees the panel selected.
+    pub fn new_device(dev: DEV, dc: DC, rst: RST, busy: BUSY) -> Self {
+        Self::new(DeviceSpi(dev), NoCs, dc, rst, busy)
+    }
+}
+
 impl<SPI, CS, DC, RST, BUSY> EpdBus<SPI, CS, DC, RST, BUSY>
 where
     SPI: SpiBus<u8> + SpiErrorType,
-    CS: OutputPin + DigitalErrorType,
-    DC: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-    RST: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-    BUSY: InputPin + Wait + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
+    CS: OutputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    BUSY: BusyLine,
 {
     pub async fn reset(
         &mut self,
-        t1_high_ms: u64, // e.g. 200
-        t_low_ms: u64,   // e.g. 2
-        t2_high_ms: u64, // e.g. 200
-    ) -> Result<(), EpdBusError<<SPI as SpiErrorType>::Error, <CS as DigitalErrorType>::Error>>
-    {
-        self.rst.set_high().map_err(EpdBusError::Gpio)?;
-        Timer::after(Duration::from_millis(t1_high_ms)).await;
-        self.rst.set_low().map_err(EpdBusError::Gpio)?;
-        Timer::after(Duration::from_millis(t_low_ms)).await;
-        self.rst.set_high().map_err(EpdBusError::Gpio)?;
-        Timer::after(Duration::from_millis(t2_high_ms)).await;
+        t1_high_ms: u32, // e.g. 200
+        t_low_ms: u32,   // e.g. 2
+        t2_high_ms: u32, // e.g. 200
+    ) -> Result<(), BusError<SPI, CS, DC, RST, BUSY>> {
+        self.rst.set_high().map_err(EpdBusError::Rst)?;
+        self.busy.delay_ms(t1_high_ms).await;
+        self.rst.set_low().map_err(EpdBusError::Rst)?;
+        self.busy.delay_ms(t_low_ms).await;
+        self.rst.set_high().map_err(EpdBusError::Rst)?;
+        self.busy.delay_ms(t2_high_ms).await;
         Ok(())
     }
 
     pub async fn write_cmd<C: Into<u8>>(
         &mut self,
         cmd: C,
-    ) -> Result<(), EpdBusError<<SPI as SpiErrorType>::Error, <CS as DigitalErrorType>::Error>>
-    {
+    ) -> Result<(), BusError<SPI, CS, DC, RST, BUSY>> {
         let byte: u8 = cmd.into();
-        self.dc.set_low().map_err(EpdBusError::Gpio)?;
-        self.cs.set_low().map_err(EpdBusError::Gpio)?;
+        self.dc.set_low().map_err(EpdBusError::Dc)?;
+        self.cs.set_low().map_err(EpdBusError::Cs)?;
         let r = self.spi.write(&[byte]).await;
         // always release CS even if SPI fails
-        let cs_res = self.cs.set_high().map_err(EpdBusError::Gpio);
+        let cs_res = self.cs.set_high().map_err(EpdBusError::Cs);
         r.map_err(EpdBusError::Spi)?;
         cs_res?;
         Ok(())
@@ -73,25 +156,199 @@ where
     pub async fn write_data(
         &mut self,
         data: &[u8],
-    ) -> Result<(), EpdBusError<<SPI as SpiErrorType>::Error, <CS as DigitalErrorType>::Error>>
-    {
+    ) -> Result<(), BusError<SPI, CS, DC, RST, BUSY>> {
         if data.is_empty() {
             return Ok(());
         }
-        self.dc.set_high().map_err(EpdBusError::Gpio)?;
-        self.cs.set_low().map_err(EpdBusError::Gpio)?;
+        self.dc.set_high().map_err(EpdBusError::Dc)?;
+        self.cs.set_low().map_err(EpdBusError::Cs)?;
         let r = self.spi.write(data).await;
-        let cs_res = self.cs.set_high().map_err(EpdBusError::Gpio);
+        let cs_res = self.cs.set_high().map_err(EpdBusError::Cs);
+        r.map_err(EpdBusError::Spi)?;
+        cs_res?;
+        Ok(())
+    }
+
+    /// Send `len` data bytes of `byte` from one `CHUNK`-sized block, so
+    /// filling a whole plane needs no frame-sized buffer.
+    pub async fn write_fill(
+        &mut self,
+        byte: u8,
+        len: usize,
+    ) -> Result<(), BusError<SPI, CS, DC, RST, BUSY>> {
+        let block = [byte; CHUNK];
+        let mut left = len;
+        while left > 0 {
+            let n = left.min(CHUNK);
+            self.write_data(&block[..n]).await?;
+            left -= n;
+        }
+        Ok(())
+    }
+
+    /// Clock `buf.len()` bytes back from the controller with DC high.
+    ///
+    /// The UC8179 answers on the same SDA line it receives on (3-wire
+    /// half-duplex), so the SPI peripheral must be able to read that line:
+    /// wire MISO to SDA through a series resistor, or use a HAL whose SPI
+    /// supports bidirectional mode. A `new_txonly` bus cannot read.
+    pub async fn read_data(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<(), BusError<SPI, CS, DC, RST, BUSY>> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+        self.dc.set_high().map_err(EpdBusError::Dc)?;
+        self.cs.set_low().map_err(EpdBusError::Cs)?;
+        let r = self.spi.read(buf).await;
+        let cs_res = self.cs.set_high().map_err(EpdBusError::Cs);
         r.map_err(EpdBusError::Spi)?;
         cs_res?;
         Ok(())
     }
 
+    /// Wait up to `timeout` for BUSY to go high (idle on the UC8179).
+    /// Returns `Ok(false)` on timeout.
     pub async fn wait(
         &mut self,
-    ) -> Result<(), EpdBusError<<SPI as SpiErrorType>::Error, <CS as DigitalErrorType>::Error>>
-    {
-        self.busy.wait_for_high().await.map_err(EpdBusError::Gpio)?;
+        timeout: Duration,
+    ) -> Result<bool, BusError<SPI, CS, DC, RST, BUSY>> {
+        self.busy
+            .wait_level(true, timeout)
+            .await
+            .map_err(EpdBusError::Busy)
+    }
+
+    /// Wait up to `timeout` for an active-high BUSY line, as on the SSD16xx,
+    /// to drop. Returns `Ok(false)` on timeout.
+    pub async fn wait_low(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<bool, BusError<SPI, CS, DC, RST, BUSY>> {
+        self.busy
+            .wait_level(false, timeout)
+            .await
+            .map_err(EpdBusError::Busy)
+    }
+
+    /// Pulse RST with the timing every UC8179 driver uses, then wait for
+    /// the controller to come up. `led` is lit while waiting.
+    pub async fn hw_reset<LED: OutputPin>(
+        &mut self,
+        led: &mut LED,
+        timeouts: &Timeouts,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.reset(20, 2, 20).await?;
+        self.wait_idle(led, timeouts, BusyOp::Reset).await
+    }
+
+    /// Bounded wait for a UC8179 to go idle: ask for its status, give it
+    /// 20 ms to pull BUSY low, then wait for BUSY to rise within the
+    /// timeout for `op`. `led` is lit while waiting.
+    pub async fn wait_idle<LED: OutputPin>(
+        &mut self,
+        led: &mut LED,
+        timeouts: &Timeouts,
+        op: BusyOp,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        let _ = led.set_high();
+        let r = match self.write_cmd(Command::GetStatus).await {
+            Ok(()) => {
+                self.delay_ms(20).await;
+                self.wait(timeouts.for_op(op)).await
+            }
+            Err(e) => Err(e),
+        };
+        let _ = led.set_low();
+        Self::bounded(r, op)
+    }
+
+    /// Bounded wait for an active-high BUSY line, as on the SSD16xx, to
+    /// drop within the timeout for `op`. `led` is lit while waiting.
+    pub async fn wait_idle_low<LED: OutputPin>(
+        &mut self,
+        led: &mut LED,
+        timeouts: &Timeouts,
+        op: BusyOp,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        let _ = led.set_high();
+        let r = self.wait_low(timeouts.for_op(op)).await;
+        let _ = led.set_low();
+        Self::bounded(r, op)
+    }
+
+    fn bounded(
+        r: Result<bool, BusError<SPI, CS, DC, RST, BUSY>>,
+        op: BusyOp,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        match r {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(EpdDriverError::Timeout { op }),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn delay_ms(&mut self, ms: u32) {
+        self.busy.delay_ms(ms).await;
+    }
+
+    /// Current time from the BUSY line's clock, if it has one.
+    pub fn now(&self) -> Option<Instant> {
+        self.busy.now()
+    }
+}
+
+/// An `SpiDevice` presented as a bus; each call is its own transaction.
+pub struct DeviceSpi<D>(D);
+
+impl<D> DeviceSpi<D> {
+    pub fn release(self) -> D {
+        self.0
+    }
+}
+
+impl<D: SpiErrorType> SpiErrorType for DeviceSpi<D> {
+    type Error = D::Error;
+}
+
+impl<D: SpiDevice<u8>> SpiBus<u8> for DeviceSpi<D> {
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.0.read(words).await
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        self.0.write(words).await
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        self.0.transfer(read, write).await
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.0.transfer_in_place(words).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        // Every transaction is flushed before the device releases CS.
+        Ok(())
+    }
+}
+
+/// Placeholder CS pin for [`EpdBus::new_device`], where the `SpiDevice`
+/// owns chip select.
+pub struct NoCs;
+
+impl DigitalErrorType for NoCs {
+    type Error = Infallible;
+}
+
+impl OutputPin for NoCs {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
         Ok(())
     }
 }
@@ -38,3 +38,77 @@ impl From<Command> for u8 {
         c as u8
     }
 }
+
+impl TryFrom<u8> for Command {
+    type Error = u8;
+
+    fn try_from(b: u8) -> Result<Self, u8> {
+        use Command::*;
+        Ok(match b {
+            0x00 => PanelSetting,
+            0x01 => PowerSetting,
+            0x02 => PowerOff,
+            0x04 => PowerOn,
+            0x06 => Btst,
+            0x07 => DeepSleep,
+            0x10 => DataStartTransmission1,
+            0x12 => DisplayRefresh,
+            0x13 => DataStartTransmission2,
+            0x15 => DualSPI,
+            0x20 => Vcom,
+            0x21 => LutWw,
+            0x22 => LutBw,
+            0x23 => LutWb,
+            0x24 => LutBb,
+            0x2A => LutOpt,
+            0x30 => Pll,
+            0x40 => Tsc,
+            0x41 => Tse,
+            0x50 => VcomAndDataInterval,
+            0x52 => Evs,
+            0x60 => TconSetting,
+            0x61 => TRes,
+            0x65 => ResolutionSetting,
+            0x71 => GetStatus,
+            0x82 => VcomDc,
+            0x90 => PartialWindow,
+            0x91 => PartialIn,
+            0x92 => PartialOut,
+            other => return Err(other),
+        })
+    }
+}
+
+/// Command set of the Solomon SSD16xx family (SSD1677, SSD1680, SSD1681,
+/// SSD1683).
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SsdCommand {
+    DriverOutputControl = 0x01,
+    GateVoltage = 0x03,
+    SourceVoltage = 0x04,
+    BoosterSoftStart = 0x0C,
+    DeepSleep = 0x10,
+    DataEntryMode = 0x11,
+    SwReset = 0x12,
+    TempSensor = 0x18,
+    MasterActivation = 0x20,
+    DisplayUpdateControl1 = 0x21,
+    DisplayUpdateControl2 = 0x22,
+    WriteRamBw = 0x24,
+    WriteRamRed = 0x26,
+    WriteVcom = 0x2C,
+    WriteLut = 0x32,
+    BorderWaveform = 0x3C,
+    RamXRange = 0x44,
+    RamYRange = 0x45,
+    RamXCounter = 0x4E,
+    RamYCounter = 0x4F,
+}
+
+impl From<SsdCommand> for u8 {
+    #[inline]
+    fn from(c: SsdCommand) -> Self {
+        c as u8
+    }
+}
@@ -0,0 +1,190 @@
+//! Per-panel tuning applied by `Epd::init_with`.
+
+/// Frame rate selected by the `Pll` register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameRate {
+    Hz5,
+    Hz10,
+    Hz15,
+    Hz20,
+    Hz30,
+    Hz40,
+    Hz50,
+    Hz60,
+    Hz70,
+    Hz80,
+    Hz90,
+    Hz100,
+    Hz110,
+    Hz130,
+    Hz200,
+}
+
+impl FrameRate {
+    const ALL: [FrameRate; 15] = [
+        FrameRate::Hz5,
+        FrameRate::Hz10,
+        FrameRate::Hz15,
+        FrameRate::Hz20,
+        FrameRate::Hz30,
+        FrameRate::Hz40,
+        FrameRate::Hz50,
+        FrameRate::Hz60,
+        FrameRate::Hz70,
+        FrameRate::Hz80,
+        FrameRate::Hz90,
+        FrameRate::Hz100,
+        FrameRate::Hz110,
+        FrameRate::Hz130,
+        FrameRate::Hz200,
+    ];
+
+    /// Value of the `Pll` register (FRS, bits 3:0).
+    pub const fn pll(self) -> u8 {
+        self as u8
+    }
+
+    /// The rate selected by a `Pll` register value, if it is not reserved.
+    pub fn from_pll(pll: u8) -> Option<Self> {
+        Self::ALL.get((pll & 0x0F) as usize).copied()
+    }
+
+    pub const fn hz(self) -> u32 {
+        match self {
+            FrameRate::Hz5 => 5,
+            FrameRate::Hz10 => 10,
+            FrameRate::Hz15 => 15,
+            FrameRate::Hz20 => 20,
+            FrameRate::Hz30 => 30,
+            FrameRate::Hz40 => 40,
+            FrameRate::Hz50 => 50,
+            FrameRate::Hz60 => 60,
+            FrameRate::Hz70 => 70,
+            FrameRate::Hz80 => 80,
+            FrameRate::Hz90 => 90,
+            FrameRate::Hz100 => 100,
+            FrameRate::Hz110 => 110,
+            FrameRate::Hz130 => 130,
+            FrameRate::Hz200 => 200,
+        }
+    }
+}
+
+/// What the border around the active area shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Border {
+    White,
+    Black,
+    /// Not driven; the border keeps whatever it showed.
+    Floating,
+}
+
+impl Border {
+    /// BDZ (bit 7) and BDV (bits 5:4) of `VcomAndDataInterval` for the
+    /// panel's DDX (bits 1:0). DDX[0] inverts the data polarity, and the
+    /// border levels with it.
+    pub(crate) const fn bits(self, ddx: u8) -> u8 {
+        let inverted = ddx & 0x01 != 0;
+        match (self, inverted) {
+            (Border::Floating, _) => 0x80,
+            (Border::White, false) | (Border::Black, true) => 0x10,
+            (Border::Black, false) | (Border::White, true) => 0x20,
+        }
+    }
+}
+
+/// Overrides for the panel's init sequence. `None` keeps the value from the
+/// panel definition, so the default config sends exactly the bytes `init`
+/// always has.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EpdConfig {
+    /// VCOM DC level in mV, as printed on the panel's FPC (e.g. `-1900`).
+    /// Rounded to the 50 mV register steps and clamped to -100..=-4050.
+    pub vcom_mv: Option<i16>,
+    /// Booster soft-start bytes (`Btst`), phases A, B, C and the duration.
+    pub booster: Option<[u8; 4]>,
+    pub frame_rate: Option<FrameRate>,
+    pub border: Option<Border>,
+    /// `PowerSetting` bytes 2 to 4: the VGH/VGL level and the VDH and VDL
+    /// levels. The panel's supply enables (byte 1) and VDHR (byte 5) are
+    /// kept.
+    pub power: Option<[u8; 3]>,
+    /// `TconSetting` byte: source-to-gate and gate-to-source non-overlap.
+    pub tcon: Option<u8>,
+    /// VCOM and data interval (CDI, bits 3:0 of the second
+    /// `VcomAndDataInterval` byte), in hsync periods from 17 (0) to 2 (15).
+    pub data_interval: Option<u8>,
+}
+
+impl EpdConfig {
+    pub const fn new() -> Self {
+        Self {
+            vcom_mv: None,
+            booster: None,
+            frame_rate: None,
+            border: None,
+            power: None,
+            tcon: None,
+            data_interval: None,
+        }
+    }
+
+    pub const fn vcom_mv(mut self, mv: i16) -> Self {
+        self.vcom_mv = Some(mv);
+        self
+    }
+
+    pub const fn booster(mut self, btst: [u8; 4]) -> Self {
+        self.booster = Some(btst);
+        self
+    }
+
+    pub const fn frame_rate(mut self, rate: FrameRate) -> Self {
+        self.frame_rate = Some(rate);
+        self
+    }
+
+    pub const fn border(mut self, border: Border) -> Self {
+        self.border = Some(border);
+        self
+    }
+
+    /// VGH/VGL, VDH and VDL bytes; see [`EpdConfig::power`].
+    pub const fn power(mut self, levels: [u8; 3]) -> Self {
+        self.power = Some(levels);
+        self
+    }
+
+    pub const fn tcon(mut self, tcon: u8) -> Self {
+        self.tcon = Some(tcon);
+        self
+    }
+
+    pub const fn data_interval(mut self, cdi: u8) -> Self {
+        self.data_interval = Some(cdi & 0x0F);
+        self
+    }
+
+    /// `PowerSetting` bytes for `power` on top of the panel's `base` bytes,
+    /// and how many to send. Without a panel value, only the internal
+    /// supplies are enabled.
+    pub(crate) fn power_setting(&self, base: Option<&[u8]>) -> Option<([u8; 5], usize)> {
+        self.power.map(|levels| {
+            let base = base.unwrap_or(&[0x07]);
+            let mut data = [0u8; 5];
+            let n = base.len().min(data.len());
+            data[..n].copy_from_slice(&base[..n]);
+            data[1..4].copy_from_slice(&levels);
+            (data, n.max(4))
+        })
+    }
+
+    /// `VcomDc` register value for `vcom_mv`: -100 mV at 0x00, -50 mV per
+    /// step.
+    pub(crate) fn vcom_register(&self) -> Option<u8> {
+        self.vcom_mv.map(|mv| {
+            let mv = (mv as i32).clamp(-4050, -100);
+            ((-100 - mv + 25) / 50) as u8
+        })
+    }
+}
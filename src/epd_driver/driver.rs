@@ -1,119 +1,493 @@
-use embassy_time::{Duration, Timer};
-use embedded_hal::digital::{InputPin, OutputPin};
-use embedded_hal_async::digital::Wait;
+use core::marker::PhantomData;
+
+use embedded_hal::digital::OutputPin;
 use embedded_hal_async::spi::SpiBus;
 
 // Error Types
-use embedded_hal::digital::ErrorType as DigitalErrorType;
 use embedded_hal::spi::ErrorType as SpiErrorType;
 
-use super::bus::EpdBus;
+use super::Rect;
+use super::bus::{BusyLine, CHUNK, EpdBus};
 use super::command::Command;
+use super::config::EpdConfig;
 use super::error::{DriverError, EpdDriverError};
-use super::{BUF_SIZE, HEIGHT, Rect, WIDTH};
+use super::luts::{DisplayMode, TempRange, Waveform};
+use super::panel::{Panel7in5V2, Uc8179Panel};
+use super::policy::{RefreshPolicy, RefreshStats};
+use super::status::PanelStatus;
+use super::timeout::{BusyOp, Timeouts};
+use super::traits::EpdDriver;
+
+/// Driver for the 7.5" 800x480 panel.
+pub type Epd800x480<SPI, CS, DC, RST, BUSY, LED> = Epd<Panel7in5V2, SPI, CS, DC, RST, BUSY, LED>;
 
-pub struct Epd800x480<SPI, CS, DC, RST, BUSY, LED> {
+/// UC8179 driver for the panel described by `P`.
+pub struct Epd<P, SPI, CS, DC, RST, BUSY, LED> {
     pub bus: EpdBus<SPI, CS, DC, RST, BUSY>,
     led: LED,
+    timeouts: Timeouts,
+    auto_recover: bool,
+    /// LUTs loaded with `set_mode` or `set_lut`, re-applied after a
+    /// recovery.
+    pub(crate) waveform: Waveform,
+    /// Controller copies each shown frame into DTM1 (N2OCP).
+    pub(crate) differential: bool,
+    policy: RefreshPolicy,
+    stats: RefreshStats,
+    /// Temperature bucket of the currently loaded LUTs.
+    pub(crate) lut_range: TempRange,
+    /// Last known panel temperature in °C.
+    pub(crate) temperature: Option<i8>,
+    /// Overrides applied by every `init`.
+    config: EpdConfig,
+    panel: PhantomData<P>,
 }
 
-impl<SPI, CS, DC, RST, BUSY, LED> Epd800x480<SPI, CS, DC, RST, BUSY, LED>
+impl<P, SPI, CS, DC, RST, BUSY, LED> Epd<P, SPI, CS, DC, RST, BUSY, LED>
 where
+    P: Uc8179Panel,
     SPI: SpiBus<u8> + SpiErrorType,
-    CS: OutputPin + DigitalErrorType,
-    DC: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-    RST: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-    BUSY: InputPin + Wait + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
+    CS: OutputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    BUSY: BusyLine,
     LED: OutputPin,
 {
     pub fn new(bus: EpdBus<SPI, CS, DC, RST, BUSY>, led: LED) -> Self {
-        Self { bus, led }
+        let stats = RefreshStats::new(bus.now());
+        Self {
+            bus,
+            led,
+            timeouts: Timeouts::default(),
+            auto_recover: false,
+            waveform: Waveform::Otp,
+            differential: false,
+            policy: RefreshPolicy::default(),
+            stats,
+            lut_range: TempRange::Normal,
+            temperature: None,
+            config: EpdConfig::default(),
+            panel: PhantomData,
+        }
     }
 
-    pub async fn hw_reset(&mut self) -> Result<(), DriverError<SPI, CS>> {
-        self.bus
-            .reset(20, 2, 20)
-            .await
-            .map_err(EpdDriverError::from)
+    /// Set the upper bound for each kind of BUSY wait.
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
     }
 
-    pub async fn wait_ready(&mut self) -> Result<(), DriverError<SPI, CS>> {
-        let _ = self.led.set_high();
-        self.bus.write_cmd(Command::GetStatus).await?;
-        Timer::after(Duration::from_millis(20)).await;
-        match self.bus.wait().await {
-            Ok(t) => {
-                let _ = self.led.set_low();
-                Ok(t)
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    /// When enabled, a BUSY timeout triggers `init`, which also restores
+    /// the loaded waveform, before the error is returned, so the next call
+    /// starts from a known controller state.
+    pub fn set_auto_recover(&mut self, on: bool) {
+        self.auto_recover = on;
+    }
+
+    /// Set the limits after which a full cleaning refresh is inserted.
+    pub fn set_refresh_policy(&mut self, policy: RefreshPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn refresh_policy(&self) -> RefreshPolicy {
+        self.policy
+    }
+
+    pub async fn hw_reset(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.bus.hw_reset(&mut self.led, &self.timeouts).await
+    }
+
+    pub async fn wait_ready(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.wait_busy(BusyOp::Refresh).await
+    }
+
+    /// Bounded BUSY wait, with optional recovery on timeout.
+    async fn wait_busy(&mut self, op: BusyOp) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        match self.bus.wait_idle(&mut self.led, &self.timeouts, op).await {
+            Err(EpdDriverError::Timeout { op }) if self.auto_recover => {
+                let _ = self.init().await;
+                Err(EpdDriverError::Timeout { op })
             }
-            Err(e) => Err(EpdDriverError::from(e)),
+            r => r,
         }
     }
 
-    pub async fn init(&mut self) -> Result<(), DriverError<SPI, CS>> {
-        self.hw_reset().await?;
-
-        // Power Setting (PWR)
-        self.bus.write_cmd(Command::PowerSetting).await?;
-        self.bus.write_data(&[0x17, 0x17, 0x3f, 0x3f, 0x11]).await?;
+    /// Store `config` and run [`Self::init`] with it. The config is kept
+    /// for later `init` calls, including the ones made by auto-recovery.
+    pub async fn init_with(
+        &mut self,
+        config: EpdConfig,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.config = config;
+        self.init().await
+    }
 
-        // VCOM and data interval setting
-        self.bus.write_cmd(Command::VcomDc).await?;
-        self.bus.write_data(&[0x24]).await?;
+    pub fn config(&self) -> EpdConfig {
+        self.config
+    }
 
-        // Booster Soft Start
-        self.bus.write_cmd(Command::Btst).await?;
-        self.bus.write_data(&[0x27, 0x27, 0x2F, 0x17]).await?;
+    /// Reset and configure the controller.
+    ///
+    /// The reset drops the register LUTs, so the waveform of the last
+    /// `set_mode` or `set_lut` is loaded again, and the data interval is
+    /// written with the current differential setting.
+    pub async fn init(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.hw_reset().await?;
 
-        // PLL Control
-        self.bus.write_cmd(Command::Pll).await?;
-        self.bus.write_data(&[0x06]).await?; // 50Hz refresh rate
+        // Config values replace the panel's bytes for the same register;
+        // ones the panel sequence lacks are sent after it.
+        let vcom = self.config.vcom_register().map(|v| [v]);
+        let pll = self.config.frame_rate.map(|r| [r.pll()]);
+        let power = self
+            .config
+            .power_setting(panel_data(P::POWER_SEQUENCE, Command::PowerSetting));
+        let mut overrides: [(Command, Option<&[u8]>); 4] = [
+            (Command::PowerSetting, power.as_ref().map(|(p, n)| &p[..*n])),
+            (Command::VcomDc, vcom.as_ref().map(|v| &v[..])),
+            (Command::Btst, self.config.booster.as_ref().map(|b| &b[..])),
+            (Command::Pll, pll.as_ref().map(|p| &p[..])),
+        ];
+        for &(cmd, data) in P::POWER_SEQUENCE {
+            let data = match overrides.iter_mut().find(|(c, _)| *c == cmd) {
+                Some((_, o)) => o.take().unwrap_or(data),
+                None => data,
+            };
+            self.bus.write_cmd(cmd).await?;
+            self.bus.write_data(data).await?;
+        }
+        for (cmd, data) in overrides {
+            if let Some(data) = data {
+                self.bus.write_cmd(cmd).await?;
+                self.bus.write_data(data).await?;
+            }
+        }
 
         // Power On
         self.bus.write_cmd(Command::PowerOn).await?;
-        self.wait_ready().await?;
+        // No recovery here: `init` is what recovery runs.
+        self.bus
+            .wait_idle(&mut self.led, &self.timeouts, BusyOp::PowerOn)
+            .await?;
         self.bus.write_cmd(Command::PanelSetting).await?;
-        self.bus.write_data(&[0x1F]).await?;
+        self.bus.write_data(&[P::PANEL_SETTING]).await?;
         self.bus.write_cmd(Command::TRes).await?;
         self.bus
             .write_data(&[
-                hb(WIDTH as u16),
-                lb(WIDTH as u16),
-                hb(HEIGHT as u16),
-                lb(HEIGHT as u16),
+                hb(P::WIDTH as u16),
+                lb(P::WIDTH as u16),
+                hb(P::HEIGHT as u16),
+                lb(P::HEIGHT as u16),
             ])
             .await?;
-        self.bus.write_cmd(Command::DualSPI).await?;
-        self.bus.write_data(&[0x00]).await?;
+        let tcon = self.config.tcon.map(|t| [t]);
+        let mut tcon = tcon.as_ref().map(|t| &t[..]);
+        for &(cmd, data) in P::PANEL_SEQUENCE {
+            let data = match cmd {
+                Command::VcomAndDataInterval => {
+                    self.write_data_interval().await?;
+                    continue;
+                }
+                Command::TconSetting => tcon.take().unwrap_or(data),
+                _ => data,
+            };
+            self.bus.write_cmd(cmd).await?;
+            self.bus.write_data(data).await?;
+        }
+        if let Some(tcon) = tcon {
+            self.bus.write_cmd(Command::TconSetting).await?;
+            self.bus.write_data(tcon).await?;
+        }
+        if self.waveform != Waveform::Otp {
+            self.load_waveform(self.waveform).await?;
+        }
+        Ok(())
+    }
+
+    /// Enable differential updates.
+    ///
+    /// The controller is told to copy every shown frame from the "new"
+    /// (DTM2) plane into the "old" (DTM1) plane after each refresh, so
+    /// `display` only sends the new frame and pixels that keep their color
+    /// get the WW/BB LUTs instead of BW/WB. That only helps with a waveform
+    /// whose WW/BB are lighter than BW/WB: `DisplayMode::Terminal`, or a
+    /// custom set with [`LutSet::has_transition_luts`]. Enabling it with
+    /// any other waveform loaded, including OTP, fails with
+    /// `NotDifferential`, as does loading such a waveform while it is on.
+    ///
+    /// Do one `clear` or `display_diff` after enabling so the old plane
+    /// matches the screen.
+    ///
+    /// [`LutSet::has_transition_luts`]: super::LutSet::has_transition_luts
+    pub async fn set_differential(
+        &mut self,
+        on: bool,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        if on && !self.waveform.supports_differential() {
+            return Err(EpdDriverError::NotDifferential);
+        }
+        self.differential = on;
+        self.write_data_interval().await
+    }
+
+    async fn write_data_interval(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        // The panel's value with the configured border and interval, plus
+        // N2OCP (bit 3) when differential.
+        let Some(base) = panel_data(P::PANEL_SEQUENCE, Command::VcomAndDataInterval) else {
+            return Ok(());
+        };
+        let mut cdi = [0u8; 2];
+        let mut n = base.len().min(cdi.len());
+        cdi[..n].copy_from_slice(&base[..n]);
+        if let Some(border) = self.config.border {
+            cdi[0] = (cdi[0] & !0xB0) | border.bits(cdi[0] & 0x03);
+        }
+        if let Some(interval) = self.config.data_interval {
+            cdi[1] = (cdi[1] & !0x0F) | interval;
+            n = 2;
+        }
+        if self.differential {
+            cdi[0] |= 0x08;
+        }
         self.bus.write_cmd(Command::VcomAndDataInterval).await?;
-        self.bus.write_data(&[0x10, 0x07]).await?;
-        self.bus.write_cmd(Command::TconSetting).await?;
-        self.bus.write_data(&[0x22]).await?;
+        self.bus.write_data(&cdi[..n]).await?;
         Ok(())
     }
 
-    pub async fn display(&mut self, buf: &[u8]) -> Result<(), DriverError<SPI, CS>> {
+    pub async fn display(&mut self, buf: &[u8]) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        if buf.len() != P::BUF_SIZE {
+            return Err(EpdDriverError::BadBufferLen {
+                expected: P::BUF_SIZE,
+                got: buf.len(),
+            });
+        }
         self.wait_ready().await?;
-        if buf.len() != BUF_SIZE {
+        if !self.differential {
+            self.bus.write_cmd(Command::DataStartTransmission1).await?;
+            self.send_zeros(P::BUF_SIZE).await?;
+        }
+        self.bus.write_cmd(Command::DataStartTransmission2).await?;
+        self.bus.write_data(buf).await?;
+        self.refresh().await?;
+        self.after_full().await
+    }
+
+    /// Full refresh with the frame generated one row at a time, for when
+    /// there is no room for a whole frame.
+    ///
+    /// `fill(y, row)` writes row `y` into `row`: `P::WIDTH / 8` bytes,
+    /// MSB-first, `1` = black, zeroed beforehand. Rows are collected into
+    /// small chunks and sent as they fill up, so only one chunk is held in
+    /// memory.
+    pub async fn display_rows<F>(
+        &mut self,
+        mut fill: F,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>>
+    where
+        F: FnMut(usize, &mut [u8]),
+    {
+        let row_bytes = P::WIDTH / 8;
+        const { assert!(P::WIDTH / 8 <= CHUNK, "panel too wide for row streaming") };
+        self.wait_ready().await?;
+        if !self.differential {
+            self.bus.write_cmd(Command::DataStartTransmission1).await?;
+            self.send_zeros(P::BUF_SIZE).await?;
+        }
+        self.bus.write_cmd(Command::DataStartTransmission2).await?;
+        let rows_per_chunk = CHUNK / row_bytes;
+        let mut chunk = [0u8; CHUNK];
+        let mut y = 0;
+        while y < P::HEIGHT {
+            let n = rows_per_chunk.min(P::HEIGHT - y);
+            let out = &mut chunk[..n * row_bytes];
+            out.fill(0);
+            for (i, row) in out.chunks_exact_mut(row_bytes).enumerate() {
+                fill(y + i, row);
+            }
+            self.bus.write_data(out).await?;
+            y += n;
+        }
+        self.refresh().await?;
+        self.after_full().await
+    }
+
+    /// Full refresh with the pixels of `pixels`, row-major from the top
+    /// left, `true` = black. Pixels past the end of the iterator are white.
+    pub async fn display_iter<I>(
+        &mut self,
+        pixels: I,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>>
+    where
+        I: IntoIterator<Item = bool>,
+    {
+        let mut pixels = pixels.into_iter();
+        self.display_rows(|_, row| {
+            for byte in row {
+                for (bit, on) in pixels.by_ref().take(8).enumerate() {
+                    if on {
+                        *byte |= 0x80 >> bit;
+                    }
+                }
+            }
+        })
+        .await
+    }
+
+    /// Full-screen update with an explicit previous frame: pixels that
+    /// differ between `old` and `new` get the BW/WB LUTs, the others WW/BB.
+    pub async fn display_diff(
+        &mut self,
+        old: &[u8],
+        new: &[u8],
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        for buf in [old, new] {
+            if buf.len() != P::BUF_SIZE {
+                return Err(EpdDriverError::BadBufferLen {
+                    expected: P::BUF_SIZE,
+                    got: buf.len(),
+                });
+            }
+        }
+        self.wait_ready().await?;
+        self.bus.write_cmd(Command::DataStartTransmission1).await?;
+        self.bus.write_data(old).await?;
+        self.bus.write_cmd(Command::DataStartTransmission2).await?;
+        self.bus.write_data(new).await?;
+        self.refresh().await?;
+        self.after_full().await
+    }
+
+    /// Show a four-level grayscale frame.
+    ///
+    /// `buf` is 2bpp, row-major, four pixels per byte MSB-first, each the
+    /// `Gray2` luma (0 = black, 3 = white). The darkness high bit goes to
+    /// DTM1 and the low bit to DTM2 so the `Gray4` LUTs select the level.
+    /// Loads the `Gray4` LUTs for the refresh and restores the previous mode
+    /// afterwards. Counts towards the [`RefreshPolicy`] like a `display`
+    /// with a fast mode, but never cleans by itself: an `Official` refresh
+    /// over the gray planes would only show the low bit. Once the limit is
+    /// reached, the next 1bpp `display` or `display_partial` cleans.
+    ///
+    /// In differential mode the controller copies the low-bit plane into
+    /// DTM1 after the refresh, which is not what the screen shows, so the
+    /// high-bit plane is sent to DTM1 again: the next `display` then treats
+    /// black and dark gray as black and the lighter levels as white.
+    pub async fn display_gray(
+        &mut self,
+        buf: &[u8],
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        if buf.len() != P::GRAY_BUF_SIZE {
             return Err(EpdDriverError::BadBufferLen {
-                expected: BUF_SIZE,
+                expected: P::GRAY_BUF_SIZE,
                 got: buf.len(),
             });
         }
+        let prev = self.waveform;
+        if prev != Waveform::Mode(DisplayMode::Gray4) {
+            self.load_waveform(Waveform::Mode(DisplayMode::Gray4))
+                .await?;
+        }
+        self.wait_ready().await?;
         self.bus.write_cmd(Command::DataStartTransmission1).await?;
-        self.send_zeros(BUF_SIZE).await?;
+        self.send_gray_plane(buf, true).await?;
         self.bus.write_cmd(Command::DataStartTransmission2).await?;
-        self.bus.write_data(buf).await?;
+        self.send_gray_plane(buf, false).await?;
         self.refresh().await?;
+        if self.differential {
+            self.bus.write_cmd(Command::DataStartTransmission1).await?;
+            self.send_gray_plane(buf, true).await?;
+        }
+        self.stats.record(Rect {
+            x: 0,
+            y: 0,
+            w: P::WIDTH,
+            h: P::HEIGHT,
+        });
+        if prev != Waveform::Mode(DisplayMode::Gray4) {
+            self.load_waveform(prev).await?;
+        }
+        Ok(())
+    }
+
+    /// Stream one bit plane of a 2bpp frame in small chunks.
+    async fn send_gray_plane(
+        &mut self,
+        buf: &[u8],
+        high: bool,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        let mut chunk = [0u8; CHUNK];
+        for src in buf.chunks(2 * CHUNK) {
+            let n = src.len() / 2;
+            for (out, pair) in chunk.iter_mut().zip(src.chunks_exact(2)) {
+                *out = gray_plane_byte(pair[0], pair[1], high);
+            }
+            self.bus.write_data(&chunk[..n]).await?;
+        }
         Ok(())
     }
 
+    /// Partial refresh of `r` with `buf` holding just that window. The
+    /// controller only addresses whole bytes, so `r.x` and `r.w` must be
+    /// multiples of 8; pad unaligned windows from what is on screen with
+    /// `Framebuffer::blit` or `pack_bitmap_onto`.
+    ///
+    /// Windows that are empty, off screen or unaligned, and buffers that are
+    /// not [`Rect::buf_len`] bytes, are rejected before anything is sent.
     pub async fn display_partial(
         &mut self,
         buf: &[u8],
         r: Rect,
-    ) -> Result<(), DriverError<SPI, CS>> {
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        r.check::<P>()?;
+        if buf.len() != r.buf_len() {
+            return Err(EpdDriverError::BadBufferLen {
+                expected: r.buf_len(),
+                got: buf.len(),
+            });
+        }
+        self.wait_ready().await?;
+        self.partial_window(r).await?;
+        self.bus.write_cmd(Command::DataStartTransmission2).await?;
+        self.bus.write_data(buf).await?;
+        self.refresh().await?;
+        self.bus.write_cmd(Command::PartialOut).await?;
+        self.after_partial(r).await
+    }
+
+    /// Partial refresh of `r`, taking its pixels straight from the
+    /// full-screen buffer `fb` one row at a time (no intermediate copy).
+    /// `r.x` and `r.w` must be multiples of 8.
+    ///
+    /// Windows that are empty, off screen or unaligned are rejected before
+    /// anything is sent.
+    pub async fn display_region(
+        &mut self,
+        fb: &[u8],
+        r: Rect,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        if fb.len() != P::BUF_SIZE {
+            return Err(EpdDriverError::BadBufferLen {
+                expected: P::BUF_SIZE,
+                got: fb.len(),
+            });
+        }
+        r.check::<P>()?;
         self.wait_ready().await?;
+        self.partial_window(r).await?;
+        self.bus.write_cmd(Command::DataStartTransmission2).await?;
+        let row_bytes = P::WIDTH / 8;
+        for y in r.y..r.y + r.h {
+            let start = y * row_bytes + r.x / 8;
+            self.bus.write_data(&fb[start..start + r.w / 8]).await?;
+        }
+        self.refresh().await?;
+        self.bus.write_cmd(Command::PartialOut).await?;
+        self.after_partial(r).await
+    }
+
+    async fn partial_window(&mut self, r: Rect) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
         let x_start = r.x;
         let x_end = r.x + r.w - 1;
         let y_start = r.y;
@@ -133,49 +507,179 @@ where
                 0x01,
             ])
             .await?;
-        self.bus.write_cmd(Command::DataStartTransmission2).await?;
-        self.bus.write_data(buf).await?;
-        self.refresh().await?;
-        self.bus.write_cmd(Command::PartialOut).await?;
         Ok(())
     }
 
-    pub async fn clear(&mut self) -> Result<(), DriverError<SPI, CS>> {
+    pub async fn clear(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
         self.wait_ready().await?;
         self.bus.write_cmd(Command::DataStartTransmission1).await?;
-        self.send_zeros(BUF_SIZE).await?;
+        if self.differential {
+            // An all-black old plane makes every pixel take the full BW
+            // swing; old = new = white would leave black pixels to the
+            // light WW pulse.
+            self.bus.write_fill(0xFF, P::BUF_SIZE).await?;
+        } else {
+            self.send_zeros(P::BUF_SIZE).await?;
+        }
         self.bus.write_cmd(Command::DataStartTransmission2).await?;
-        self.send_zeros(BUF_SIZE).await?;
+        self.send_zeros(P::BUF_SIZE).await?;
         self.refresh().await?;
-        Ok(())
+        self.after_full().await
     }
 
-    pub async fn sleep(&mut self) -> Result<(), DriverError<SPI, CS>> {
+    pub async fn sleep(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
         self.bus.write_cmd(Command::PowerOff).await?;
-        self.wait_ready().await?;
+        self.wait_busy(BusyOp::PowerOff).await?;
         self.bus.write_cmd(Command::DeepSleep).await?;
         self.bus.write_data(&[0xA5]).await?;
         Ok(())
     }
 
+    /// Read the controller status register (see [`EpdBus::read_data`] for the
+    /// wiring a read needs).
+    pub async fn read_status(
+        &mut self,
+    ) -> Result<PanelStatus, DriverError<SPI, CS, DC, RST, BUSY>> {
+        let mut flg = [0u8; 1];
+        self.bus.write_cmd(Command::GetStatus).await?;
+        self.bus.read_data(&mut flg).await?;
+        Ok(PanelStatus::from_bits_retain(flg[0]))
+    }
+
+    /// Read the on-chip temperature sensor in whole degrees Celsius. The
+    /// panel must be powered on (after `init`) for the sensor to run.
+    pub async fn read_temperature(&mut self) -> Result<i8, DriverError<SPI, CS, DC, RST, BUSY>> {
+        // TS[7:0] is the signed integer part; the second byte holds 0.5 °C
+        // in its MSB, which we drop.
+        let mut ts = [0u8; 2];
+        self.bus.write_cmd(Command::Tsc).await?;
+        self.bus.read_data(&mut ts).await?;
+        Ok(ts[0] as i8)
+    }
+
     pub async fn flash_led(&mut self) {
-        Timer::after(Duration::from_millis(500)).await;
+        self.bus.delay_ms(500).await;
         let _ = self.led.set_high();
-        Timer::after(Duration::from_millis(500)).await;
+        self.bus.delay_ms(500).await;
         let _ = self.led.set_low();
     }
 
-    async fn refresh(&mut self) -> Result<(), DriverError<SPI, CS>> {
+    /// Re-drive the whole screen from the controller's current image with
+    /// the `Official` waveform, then restore the previously loaded mode.
+    /// The controller keeps the last frame in its RAM, so no data is resent.
+    pub async fn clean(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        let prev = self.waveform;
+        let switch = !matches!(prev, Waveform::Otp | Waveform::Mode(DisplayMode::Official));
+        if switch {
+            self.load_waveform(Waveform::Mode(DisplayMode::Official))
+                .await?;
+        }
+        self.wait_ready().await?;
+        self.refresh().await?;
+        if switch {
+            self.load_waveform(prev).await?;
+        }
+        self.stats = RefreshStats::new(self.bus.now());
+        Ok(())
+    }
+
+    async fn after_partial(&mut self, r: Rect) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.stats.record(r);
+        if self.stats.exceeds(&self.policy, self.bus.now()) {
+            self.clean().await?;
+        }
+        Ok(())
+    }
+
+    /// A full refresh with the official (or OTP) waveform cleans the panel;
+    /// with a fast mode it counts like a full-screen partial update.
+    async fn after_full(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        match self.waveform {
+            Waveform::Otp | Waveform::Mode(DisplayMode::Official) => {
+                self.stats = RefreshStats::new(self.bus.now());
+                Ok(())
+            }
+            _ => {
+                self.after_partial(Rect {
+                    x: 0,
+                    y: 0,
+                    w: P::WIDTH,
+                    h: P::HEIGHT,
+                })
+                .await
+            }
+        }
+    }
+
+    async fn refresh(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
         self.bus.write_cmd(Command::DisplayRefresh).await?;
         self.wait_ready().await
     }
-    async fn send_zeros(&mut self, total: usize) -> Result<(), DriverError<SPI, CS>> {
-        let block = [0u8; HEIGHT * WIDTH / 8]; // maximum size is height * width / 8
-        self.bus.write_data(&block[..total]).await?;
-        Ok(())
+    /// Send `total` zero bytes without a frame-sized buffer.
+    pub(crate) async fn send_zeros(
+        &mut self,
+        total: usize,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        Ok(self.bus.write_fill(0, total).await?)
     }
 }
 
+impl<P, SPI, CS, DC, RST, BUSY, LED> EpdDriver for Epd<P, SPI, CS, DC, RST, BUSY, LED>
+where
+    P: Uc8179Panel,
+    SPI: SpiBus<u8> + SpiErrorType,
+    CS: OutputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    BUSY: BusyLine,
+    LED: OutputPin,
+{
+    type Panel = P;
+    type Error = DriverError<SPI, CS, DC, RST, BUSY>;
+
+    async fn init(&mut self) -> Result<(), Self::Error> {
+        Epd::init(self).await
+    }
+
+    async fn display(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        Epd::display(self, buf).await
+    }
+
+    async fn display_partial(&mut self, buf: &[u8], r: Rect) -> Result<(), Self::Error> {
+        Epd::display_partial(self, buf, r).await
+    }
+
+    async fn display_region(&mut self, fb: &[u8], r: Rect) -> Result<(), Self::Error> {
+        Epd::display_region(self, fb, r).await
+    }
+
+    async fn clear(&mut self) -> Result<(), Self::Error> {
+        Epd::clear(self).await
+    }
+
+    async fn sleep(&mut self) -> Result<(), Self::Error> {
+        Epd::sleep(self).await
+    }
+}
+
+/// Pack one plane bit for the 8 pixels held in two 2bpp bytes.
+fn gray_plane_byte(a: u8, b: u8, high: bool) -> u8 {
+    let mut out = 0u8;
+    for (k, byte) in [a, b].into_iter().enumerate() {
+        for p in 0..4 {
+            let dark = 3 - ((byte >> (6 - 2 * p)) & 0b11);
+            let bit = if high { dark >> 1 } else { dark & 1 };
+            out |= bit << (7 - (k * 4 + p));
+        }
+    }
+    out
+}
+
+/// Data the panel sends with `cmd` in `seq`, if it sends it at all.
+fn panel_data(seq: &'static [(Command, &'static [u8])], cmd: Command) -> Option<&'static [u8]> {
+    seq.iter().find(|(c, _)| *c == cmd).map(|(_, d)| *d)
+}
+
 #[inline(always)]
 fn hb(x: u16) -> u8 {
     (x >> 8) as u8
@@ -1,18 +1,54 @@
 use super::bus::EpdBusError;
+use super::rect::RectError;
+use super::timeout::BusyOp;
 use embedded_hal::digital::ErrorType as DigitalErrorType;
 use embedded_hal::spi::ErrorType as SpiErrorType;
 
 #[derive(Debug)]
-pub enum EpdDriverError<SpiE, GpioE> {
-    Bus(EpdBusError<SpiE, GpioE>),
-    BadBufferLen { expected: usize, got: usize },
+pub enum EpdDriverError<SpiE, CsE, DcE, RstE, BusyE> {
+    Bus(EpdBusError<SpiE, CsE, DcE, RstE, BusyE>),
+    BadBufferLen {
+        expected: usize,
+        got: usize,
+    },
+    /// BUSY did not go high within the configured timeout.
+    Timeout {
+        op: BusyOp,
+    },
+    /// A partial window reaches past the edge of the panel.
+    RectOutOfBounds,
+    /// A partial window with no pixels.
+    EmptyRect,
+    /// A partial window whose `x` or `w` is not a multiple of the panel's
+    /// `X_ALIGN`.
+    Misaligned,
+    /// Differential updates combined with a waveform that drives unchanged
+    /// pixels like changed ones; see `Epd::set_differential`.
+    NotDifferential,
 }
 
-impl<SpiE, GpioE> From<EpdBusError<SpiE, GpioE>> for EpdDriverError<SpiE, GpioE> {
-    fn from(e: EpdBusError<SpiE, GpioE>) -> Self {
+impl<SpiE, CsE, DcE, RstE, BusyE> From<EpdBusError<SpiE, CsE, DcE, RstE, BusyE>>
+    for EpdDriverError<SpiE, CsE, DcE, RstE, BusyE>
+{
+    fn from(e: EpdBusError<SpiE, CsE, DcE, RstE, BusyE>) -> Self {
         Self::Bus(e)
     }
 }
 
-pub type DriverError<SPI, CS> =
-    EpdDriverError<<SPI as SpiErrorType>::Error, <CS as DigitalErrorType>::Error>;
+impl<SpiE, CsE, DcE, RstE, BusyE> From<RectError> for EpdDriverError<SpiE, CsE, DcE, RstE, BusyE> {
+    fn from(e: RectError) -> Self {
+        match e {
+            RectError::Empty => Self::EmptyRect,
+            RectError::OutOfBounds => Self::RectOutOfBounds,
+            RectError::Misaligned => Self::Misaligned,
+        }
+    }
+}
+
+pub type DriverError<SPI, CS, DC, RST, BUSY> = EpdDriverError<
+    <SPI as SpiErrorType>::Error,
+    <CS as DigitalErrorType>::Error,
+    <DC as DigitalErrorType>::Error,
+    <RST as DigitalErrorType>::Error,
+    <BUSY as DigitalErrorType>::Error,
+>;
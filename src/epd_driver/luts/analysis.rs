@@ -0,0 +1,154 @@
+//! Decoding and sanity checks for UC8179 LUTs.
+//!
+//! Each 42-byte LUT is 7 phase groups of `[levels, f0, f1, f2, f3, repeat]`:
+//! `levels` packs the drive level of the four phases, two bits each with
+//! phase 0 in the top bits, `fN` is the length of phase N in frames, and the
+//! group runs `repeat` times.
+
+use embassy_time::Duration;
+
+use super::LutSet;
+use crate::epd_driver::config::FrameRate;
+
+/// Drive level of one phase.
+///
+/// In the VCOM LUT the same codes select VCOM_DC, VCOMH (VDH + VCOM_DC),
+/// VCOML (VDL + VCOM_DC) and floating.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Level {
+    Gnd,
+    Vdh,
+    Vdl,
+    /// The red drive voltage of black/white/red panels.
+    Vdhr,
+}
+
+impl Level {
+    fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => Level::Gnd,
+            0b01 => Level::Vdh,
+            0b10 => Level::Vdl,
+            _ => Level::Vdhr,
+        }
+    }
+
+    /// Polarity relative to ground (or VCOM_DC); floating counts as 0.
+    fn sign(self) -> i32 {
+        match self {
+            Level::Vdh => 1,
+            Level::Vdl => -1,
+            Level::Gnd | Level::Vdhr => 0,
+        }
+    }
+}
+
+/// One decoded phase group.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PhaseGroup {
+    pub levels: [Level; 4],
+    pub frames: [u8; 4],
+    pub repeat: u8,
+}
+
+impl PhaseGroup {
+    pub fn decode(raw: &[u8]) -> Self {
+        Self {
+            levels: core::array::from_fn(|p| Level::from_bits(raw[0] >> (6 - 2 * p))),
+            frames: [raw[1], raw[2], raw[3], raw[4]],
+            repeat: raw[5],
+        }
+    }
+
+    /// Frames this group takes, repeats included.
+    pub fn duration_frames(&self) -> u32 {
+        self.frames.iter().map(|&f| f as u32).sum::<u32>() * self.repeat as u32
+    }
+
+    /// Frames at VDH minus frames at VDL, repeats included.
+    fn net_frames(&self) -> i32 {
+        let per_pass: i32 = self
+            .levels
+            .iter()
+            .zip(self.frames)
+            .map(|(l, f)| l.sign() * f as i32)
+            .sum();
+        per_pass * self.repeat as i32
+    }
+}
+
+/// The 7 phase groups of a LUT.
+pub fn phase_groups(lut: &[u8; 42]) -> [PhaseGroup; 7] {
+    core::array::from_fn(|g| PhaseGroup::decode(&lut[g * 6..g * 6 + 6]))
+}
+
+/// Why [`LutSet::validate`] rejected a set.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LutError {
+    /// Phase group `group` of `table` drives VDHR, which black/white
+    /// panels do not use.
+    RedVoltage { table: &'static str, group: usize },
+    /// Pixels driven by `table` see `net` more frames at VDH than at VDL
+    /// relative to VCOM (negative: more at VDL).
+    DcImbalance { table: &'static str, net: i32 },
+}
+
+const PIXEL_TABLES: [&str; 4] = ["ww", "bw", "wb", "bb"];
+
+impl LutSet {
+    /// Check that every pixel LUT is DC balanced against VCOM and stays
+    /// within the black/white drive levels.
+    ///
+    /// Balance is counted in frames, assuming VDH and VDL of equal
+    /// magnitude. Of the shipped tables only `Official` and `Gray4` are
+    /// balanced; `Fast` and `Terminal` trade balance for speed and rely on
+    /// the cleaning refresh of `RefreshPolicy`.
+    pub fn validate(&self) -> Result<(), LutError> {
+        for (table, lut) in PIXEL_TABLES.into_iter().zip(self.pixel_luts()) {
+            if let Some(group) = phase_groups(lut)
+                .iter()
+                .position(|g| g.repeat > 0 && g.levels.contains(&Level::Vdhr))
+            {
+                return Err(LutError::RedVoltage { table, group });
+            }
+        }
+        for (table, net) in PIXEL_TABLES.into_iter().zip(self.dc_offsets()) {
+            if net != 0 {
+                return Err(LutError::DcImbalance { table, net });
+            }
+        }
+        Ok(())
+    }
+
+    /// Net drive, in frames, of `ww`, `bw`, `wb` and `bb` relative to VCOM.
+    pub fn dc_offsets(&self) -> [i32; 4] {
+        let vcom = net_frames(&self.vcom);
+        self.pixel_luts().map(|lut| net_frames(lut) - vcom)
+    }
+
+    /// Length of the longest LUT in frames; the LUTs run side by side.
+    pub fn duration_frames(&self) -> u32 {
+        [&self.vcom, &self.ww, &self.bw, &self.wb, &self.bb]
+            .into_iter()
+            .map(|lut| phase_groups(lut).iter().map(|g| g.duration_frames()).sum())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// How long a refresh with this set takes at the frame rate selected by
+    /// the `Pll` register value `pll`, or `None` if `pll` is reserved.
+    pub fn estimated_refresh_time(&self, pll: u8) -> Option<Duration> {
+        let hz = FrameRate::from_pll(pll)?.hz();
+        Some(Duration::from_micros(
+            self.duration_frames() as u64 * 1_000_000 / hz as u64,
+        ))
+    }
+
+    fn pixel_luts(&self) -> [&[u8; 42]; 4] {
+        [&self.ww, &self.bw, &self.wb, &self.bb]
+    }
+}
+
+fn net_frames(lut: &[u8; 42]) -> i32 {
+    phase_groups(lut).iter().map(|g| g.net_frames()).sum()
+}
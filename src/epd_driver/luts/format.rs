@@ -0,0 +1,218 @@
+//! Text and binary encodings of a [`LutSet`], for tuning waveforms at
+//! runtime, sending them over USB or keeping them in flash.
+//!
+//! Both parsers work on borrowed input and need no allocator.
+//!
+//! # Text
+//!
+//! One table per section. A section starts with the table name and a colon,
+//! followed by its bytes in hex (`3F` or `0x3F`), separated by spaces or
+//! commas. The bytes may continue on the following lines. `#` starts a
+//! comment that runs to the end of the line. Every table must appear exactly
+//! once, in any order:
+//!
+//! ```text
+//! # fast mode, room temperature
+//! voltage_frame: 06 3F 3F 11 24 07 17
+//! vcom:
+//!   00 0F 01 0F 01 01
+//!   00 0F 0F 00 00 01
+//!   00 00 00 00 00 00   # ... 7 phase groups of 6 bytes
+//! ww: ...
+//! bw: ...
+//! wb: ...
+//! bb: ...
+//! ```
+//!
+//! `voltage_frame` holds 7 bytes and the LUTs 42 each. The `Display` impl of
+//! `LutSet` writes this format, one phase group per line.
+//!
+//! # Binary
+//!
+//! [`LutSet::BIN_LEN`] bytes: the magic `b"LUT"`, a version byte (`1`), then
+//! `voltage_frame`, `vcom`, `ww`, `bw`, `wb` and `bb` back to back.
+
+use core::fmt;
+
+use super::LutSet;
+
+const MAGIC: &[u8; 3] = b"LUT";
+const VERSION: u8 = 1;
+const NAMES: [&str; 6] = ["voltage_frame", "vcom", "ww", "bw", "wb", "bb"];
+
+const ZEROED: LutSet = LutSet {
+    voltage_frame: [0; 7],
+    vcom: [0; 42],
+    ww: [0; 42],
+    bw: [0; 42],
+    wb: [0; 42],
+    bb: [0; 42],
+};
+
+/// Why a LUT file was rejected.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LutParseError {
+    /// The binary header is not `b"LUT"` followed by a known version.
+    BadMagic,
+    /// The binary blob is not [`LutSet::BIN_LEN`] bytes long.
+    BadLength { expected: usize, got: usize },
+    /// Bytes before the first `name:` on text line `line` (1-based).
+    Syntax { line: usize },
+    /// The section name on `line` is not a table of a `LutSet`.
+    UnknownTable { line: usize },
+    /// The table on `line` was already given.
+    Duplicate { line: usize },
+    /// A token on `line` is not a hex byte.
+    BadByte { line: usize },
+    /// A table has the wrong number of bytes.
+    WrongSize {
+        table: &'static str,
+        expected: usize,
+        got: usize,
+    },
+    /// A table never appeared.
+    Missing { table: &'static str },
+}
+
+impl LutSet {
+    /// Size of the binary encoding.
+    pub const BIN_LEN: usize = 4 + 7 + 5 * 42;
+
+    /// Parse the text format.
+    pub fn from_text(text: &str) -> Result<LutSet, LutParseError> {
+        let mut set = ZEROED;
+        // Bytes seen per table, or `None` if the table has not started.
+        let mut counts: [Option<usize>; 6] = [None; 6];
+        let mut current = None;
+
+        for (n, line) in text.lines().enumerate() {
+            let line_no = n + 1;
+            let mut rest = line.split('#').next().unwrap_or("");
+            if let Some((name, bytes)) = rest.split_once(':') {
+                let t = NAMES
+                    .iter()
+                    .position(|&t| t == name.trim())
+                    .ok_or(LutParseError::UnknownTable { line: line_no })?;
+                if counts[t].is_some() {
+                    return Err(LutParseError::Duplicate { line: line_no });
+                }
+                counts[t] = Some(0);
+                current = Some(t);
+                rest = bytes;
+            }
+            for token in rest.split(|c: char| c.is_whitespace() || c == ',') {
+                if token.is_empty() {
+                    continue;
+                }
+                let t = current.ok_or(LutParseError::Syntax { line: line_no })?;
+                let digits = token
+                    .strip_prefix("0x")
+                    .or_else(|| token.strip_prefix("0X"))
+                    .unwrap_or(token);
+                let byte = u8::from_str_radix(digits, 16)
+                    .map_err(|_| LutParseError::BadByte { line: line_no })?;
+                let count = counts[t].get_or_insert(0);
+                if let Some(slot) = set.table_mut(t).get_mut(*count) {
+                    *slot = byte;
+                }
+                *count += 1;
+            }
+        }
+
+        for (t, count) in counts.iter().enumerate() {
+            let expected = set.table(t).len();
+            match *count {
+                None => return Err(LutParseError::Missing { table: NAMES[t] }),
+                Some(got) if got != expected => {
+                    return Err(LutParseError::WrongSize {
+                        table: NAMES[t],
+                        expected,
+                        got,
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(set)
+    }
+
+    /// Decode the binary format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<LutSet, LutParseError> {
+        if bytes.len() != Self::BIN_LEN {
+            return Err(LutParseError::BadLength {
+                expected: Self::BIN_LEN,
+                got: bytes.len(),
+            });
+        }
+        if &bytes[..3] != MAGIC || bytes[3] != VERSION {
+            return Err(LutParseError::BadMagic);
+        }
+        let mut set = ZEROED;
+        let mut at = 4;
+        for t in 0..NAMES.len() {
+            let table = set.table_mut(t);
+            let len = table.len();
+            table.copy_from_slice(&bytes[at..at + len]);
+            at += len;
+        }
+        Ok(set)
+    }
+
+    /// Encode as the binary format.
+    pub fn to_bytes(&self) -> [u8; Self::BIN_LEN] {
+        let mut out = [0u8; Self::BIN_LEN];
+        out[..3].copy_from_slice(MAGIC);
+        out[3] = VERSION;
+        let mut at = 4;
+        for t in 0..NAMES.len() {
+            let table = self.table(t);
+            out[at..at + table.len()].copy_from_slice(table);
+            at += table.len();
+        }
+        out
+    }
+
+    fn table(&self, t: usize) -> &[u8] {
+        match t {
+            0 => &self.voltage_frame,
+            1 => &self.vcom,
+            2 => &self.ww,
+            3 => &self.bw,
+            4 => &self.wb,
+            _ => &self.bb,
+        }
+    }
+
+    fn table_mut(&mut self, t: usize) -> &mut [u8] {
+        match t {
+            0 => &mut self.voltage_frame,
+            1 => &mut self.vcom,
+            2 => &mut self.ww,
+            3 => &mut self.bw,
+            4 => &mut self.wb,
+            _ => &mut self.bb,
+        }
+    }
+}
+
+/// Writes the text format.
+impl fmt::Display for LutSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", NAMES[0])?;
+        for b in &self.voltage_frame {
+            write!(f, " {b:02X}")?;
+        }
+        writeln!(f)?;
+        for (t, name) in NAMES.iter().enumerate().skip(1) {
+            writeln!(f, "{name}:")?;
+            for group in self.table(t).chunks(6) {
+                write!(f, " ")?;
+                for b in group {
+                    write!(f, " {b:02X}")?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use super::{LutSet, table};
+
+// Four-level grayscale. Each pixel is split over both planes: DTM1 ("old")
+// carries the high bit and DTM2 ("new") the low bit of its darkness, so the
+// controller picks WW for white, WB for light gray, BW for dark gray and BB
+// for black.
+
+const VOLTAGE_FRAME_GRAY4: [u8; 7] = [0x6, 0x3F, 0x3F, 0x11, 0x24, 0x7, 0x17];
+
+const LUT_VCOM_GRAY4: [u8; 42] = [
+    0x00, 0x0A, 0x00, 0x00, 0x00, 0x01, 0x60, 0x14, 0x14, 0x00, 0x00, 0x01, 0x00, 0x14, 0x00, 0x00,
+    0x00, 0x01, 0x00, 0x13, 0x0A, 0x01, 0x00, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0,
+];
+const LUT_WW_GRAY4: [u8; 42] = [
+    0x40, 0x0A, 0x00, 0x00, 0x00, 0x01, 0x90, 0x14, 0x14, 0x00, 0x00, 0x01, 0x10, 0x14, 0x0A, 0x00,
+    0x00, 0x01, 0xA0, 0x13, 0x01, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0,
+];
+const LUT_BW_GRAY4: [u8; 42] = [
+    0x40, 0x0A, 0x00, 0x00, 0x00, 0x01, 0x90, 0x14, 0x14, 0x00, 0x00, 0x01, 0x00, 0x14, 0x0A, 0x00,
+    0x00, 0x01, 0x99, 0x0C, 0x01, 0x03, 0x04, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0,
+];
+const LUT_WB_GRAY4: [u8; 42] = [
+    0x40, 0x0A, 0x00, 0x00, 0x00, 0x01, 0x90, 0x14, 0x14, 0x00, 0x00, 0x01, 0x00, 0x14, 0x0A, 0x00,
+    0x00, 0x01, 0x99, 0x0B, 0x04, 0x04, 0x01, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0,
+];
+const LUT_BB_GRAY4: [u8; 42] = [
+    0x80, 0x0A, 0x00, 0x00, 0x00, 0x01, 0x90, 0x14, 0x14, 0x00, 0x00, 0x01, 0x20, 0x14, 0x0A, 0x00,
+    0x00, 0x01, 0x50, 0x13, 0x01, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0,
+];
+
+pub const LUTS: LutSet = LutSet {
+    voltage_frame: VOLTAGE_FRAME_GRAY4,
+    vcom: LUT_VCOM_GRAY4,
+    ww: LUT_WW_GRAY4,
+    bw: LUT_BW_GRAY4,
+    wb: LUT_WB_GRAY4,
+    bb: LUT_BB_GRAY4,
+};
+
+pub const TABLE: [LutSet; 3] = table(LUTS);
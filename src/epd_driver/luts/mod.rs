@@ -1,13 +1,16 @@
 // LUTs for EPD
 
+pub mod analysis;
+pub mod format;
 pub mod mode;
 
 // Modes
 pub mod fast;
+pub mod gray4;
 pub mod official;
 pub mod terminal;
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct LutSet {
     pub voltage_frame: [u8; 7],
     pub vcom: [u8; 42],
@@ -17,6 +20,70 @@ pub struct LutSet {
     pub bb: [u8; 42],
 }
 
+impl LutSet {
+    /// `true` if WW and BB differ from BW and WB, i.e. pixels that keep
+    /// their color get another waveform than pixels that change. Only such
+    /// a set makes differential updates worthwhile.
+    pub fn has_transition_luts(&self) -> bool {
+        self.ww != self.bw && self.bb != self.wb
+    }
+}
+
+/// Per-temperature tables for a set tuned at room temperature, indexed
+/// like [`TempRange`]: freezing, cold, normal.
+///
+/// No set has been characterised below 10 °C yet, so the colder buckets
+/// reuse the room-temperature one. Lengthening the phases instead would
+/// scale any DC imbalance in the set by the same factor. Measured tables
+/// go in the first two slots once they exist.
+const fn table(base: LutSet) -> [LutSet; 3] {
+    [base, base, base]
+}
+
+/// The LUTs a UC8179 driver currently has loaded.
+// Held once per driver, so the size of `Custom` does not matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum Waveform {
+    /// The OTP waveform selected by `init`.
+    Otp,
+    Mode(DisplayMode),
+    /// A set loaded with `set_lut`.
+    Custom(LutSet),
+}
+
+impl Waveform {
+    /// Whether differential updates may be used with this waveform.
+    pub(crate) fn supports_differential(&self) -> bool {
+        match self {
+            Waveform::Otp => false,
+            Waveform::Mode(mode) => mode.supports_differential(),
+            Waveform::Custom(luts) => luts.has_transition_luts(),
+        }
+    }
+}
+
+/// Temperature bucket a LUT set is tuned for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TempRange {
+    /// Below 0 °C.
+    Freezing,
+    /// 0 °C to 10 °C.
+    Cold,
+    /// 10 °C and above; the range the shipped sets were tuned for.
+    Normal,
+}
+
+impl TempRange {
+    pub fn from_celsius(celsius: i8) -> Self {
+        match celsius {
+            i8::MIN..=-1 => TempRange::Freezing,
+            0..=9 => TempRange::Cold,
+            _ => TempRange::Normal,
+        }
+    }
+}
+
 /// Enum to select a display refresh mode.
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum DisplayMode {
@@ -26,14 +93,39 @@ pub enum DisplayMode {
     Fast,
     /// Terminal optimized mode.
     Terminal,
+    /// Four-level grayscale; use with `display_gray`.
+    Gray4,
 }
 
 impl DisplayMode {
+    /// Whether the mode can be used with differential updates.
+    ///
+    /// Only `Terminal` has transition LUTs: its WW/BB give unchanged pixels
+    /// a short, light pulse while BW/WB do the full swing. `Official` and
+    /// `Fast` drive WW like BW and BB like WB, so every pixel is redrawn
+    /// anyway, and `Gray4` uses the two planes for gray levels.
+    pub fn supports_differential(&self) -> bool {
+        matches!(self, DisplayMode::Terminal)
+    }
+
+    /// LUT set for room temperature.
     pub fn lut_set(&self) -> LutSet {
-        match self {
-            DisplayMode::Official => official::LUTS,
-            DisplayMode::Fast => fast::LUTS,
-            DisplayMode::Terminal => terminal::LUTS,
+        self.lut_set_for(TempRange::Normal)
+    }
+
+    /// LUT set for the given temperature bucket.
+    pub fn lut_set_for(&self, range: TempRange) -> LutSet {
+        let table = match self {
+            DisplayMode::Official => official::TABLE,
+            DisplayMode::Fast => fast::TABLE,
+            DisplayMode::Terminal => terminal::TABLE,
+            DisplayMode::Gray4 => gray4::TABLE,
+        };
+        let [freezing, cold, normal] = table;
+        match range {
+            TempRange::Freezing => freezing,
+            TempRange::Cold => cold,
+            TempRange::Normal => normal,
         }
     }
 }
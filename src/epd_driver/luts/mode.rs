@@ -1,48 +1,154 @@
 // LUTs for EPD
-use super::DisplayMode;
-use crate::epd_driver::Epd800x480;
+use super::{DisplayMode, LutSet, TempRange, Waveform};
+use crate::epd_driver::{Epd, Uc8179Panel};
 
-use embedded_hal::digital::{InputPin, OutputPin};
-use embedded_hal_async::digital::Wait;
+use embedded_hal::digital::OutputPin;
 use embedded_hal_async::spi::SpiBus;
 
-use embedded_hal::digital::ErrorType as DigitalErrorType;
 use embedded_hal::spi::ErrorType as SpiErrorType;
 
+use crate::epd_driver::bus::BusyLine;
 use crate::epd_driver::command::Command;
-use crate::epd_driver::error::DriverError;
+use crate::epd_driver::error::{DriverError, EpdDriverError};
 
-impl<SPI, CS, DC, RST, BUSY, LED> Epd800x480<SPI, CS, DC, RST, BUSY, LED>
+impl<P, SPI, CS, DC, RST, BUSY, LED> Epd<P, SPI, CS, DC, RST, BUSY, LED>
 where
+    P: Uc8179Panel,
     SPI: SpiBus<u8> + SpiErrorType,
-    CS: OutputPin + DigitalErrorType,
-    DC: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-    RST: OutputPin + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
-    BUSY: InputPin + Wait + DigitalErrorType<Error = <CS as DigitalErrorType>::Error>,
+    CS: OutputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    BUSY: BusyLine,
     LED: OutputPin,
 {
-    pub async fn set_mode(&mut self, mode: DisplayMode) -> Result<(), DriverError<SPI, CS>> {
-        let luts = mode.lut_set();
+    /// Load the LUTs for `mode`, picking the table for the last known panel
+    /// temperature (room temperature if none was supplied).
+    ///
+    /// With differential updates on, only modes that
+    /// [support them](DisplayMode::supports_differential) are accepted.
+    pub async fn set_mode(
+        &mut self,
+        mode: DisplayMode,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        if self.differential && !mode.supports_differential() {
+            return Err(EpdDriverError::NotDifferential);
+        }
+        self.load_mode(mode).await
+    }
+
+    async fn load_mode(
+        &mut self,
+        mode: DisplayMode,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        let range = self
+            .temperature
+            .map(TempRange::from_celsius)
+            .unwrap_or(TempRange::Normal);
+        self.load_luts(&mode.lut_set_for(range)).await?;
+        self.waveform = Waveform::Mode(mode);
+        self.lut_range = range;
+        Ok(())
+    }
+
+    /// Load a custom LUT set, e.g. one parsed with
+    /// [`LutSet::from_text`] or [`LutSet::from_bytes`].
+    ///
+    /// The set is used as given at any temperature, and is restored after
+    /// `display_gray`, `clean` and a recovery like a `set_mode` would be.
+    /// With differential updates on, the set must have
+    /// [transition LUTs](LutSet::has_transition_luts).
+    pub async fn set_lut(
+        &mut self,
+        luts: &LutSet,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        if self.differential && !luts.has_transition_luts() {
+            return Err(EpdDriverError::NotDifferential);
+        }
+        self.load_luts(luts).await?;
+        self.waveform = Waveform::Custom(*luts);
+        Ok(())
+    }
 
+    /// Reload the given waveform, switching back to the OTP LUTs for
+    /// [`Waveform::Otp`].
+    pub(crate) async fn load_waveform(
+        &mut self,
+        waveform: Waveform,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        match waveform {
+            Waveform::Otp => {
+                self.bus.write_cmd(Command::PanelSetting).await?;
+                self.bus.write_data(&[P::PANEL_SETTING]).await?;
+                self.waveform = Waveform::Otp;
+                Ok(())
+            }
+            Waveform::Mode(mode) => self.load_mode(mode).await,
+            Waveform::Custom(luts) => {
+                self.load_luts(&luts).await?;
+                self.waveform = waveform;
+                Ok(())
+            }
+        }
+    }
+
+    async fn load_luts(
+        &mut self,
+        luts: &LutSet,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
         // Ensure we are in Register LUT mode
         self.bus.write_cmd(Command::PanelSetting).await?;
-        self.bus.write_data(&[0x3F]).await?;
+        self.bus.write_data(&[P::PANEL_SETTING | 0x20]).await?;
 
         self.bus.write_cmd(Command::Btst).await?;
         self.bus.write_data(&luts.voltage_frame).await?;
 
-        // Load the full, official 3-phase LUTs
-        self.bus.write_cmd(Command::Vcom).await?;
-        self.bus.write_data(&luts.vcom).await?;
-        self.bus.write_cmd(Command::LutWw).await?;
-        self.bus.write_data(&luts.ww).await?;
-        self.bus.write_cmd(Command::LutBw).await?;
-        self.bus.write_data(&luts.bw).await?;
-        self.bus.write_cmd(Command::LutWb).await?;
-        self.bus.write_data(&luts.wb).await?;
-        self.bus.write_cmd(Command::LutBb).await?;
-        self.bus.write_data(&luts.bb).await?;
+        // Load the five LUT registers
+        self.write_lut(Command::Vcom, &luts.vcom, P::VCOM_LUT_LEN)
+            .await?;
+        self.write_lut(Command::LutWw, &luts.ww, P::LUT_LEN).await?;
+        self.write_lut(Command::LutBw, &luts.bw, P::LUT_LEN).await?;
+        self.write_lut(Command::LutWb, &luts.wb, P::LUT_LEN).await?;
+        self.write_lut(Command::LutBb, &luts.bb, P::LUT_LEN).await
+    }
 
+    /// Supply the panel temperature from an external sensor. If a mode is
+    /// loaded and the temperature moved to another bucket, its LUTs are
+    /// reloaded.
+    ///
+    /// The reading only picks which register LUTs the driver loads; nothing
+    /// is sent to the controller for it. `Tse` is deliberately not written:
+    /// it switches the controller to an I2C sensor on its own TSCL/TSDA
+    /// pins, which these boards leave unconnected, and the controller's
+    /// temperature only matters for the OTP waveform anyway.
+    pub async fn set_temperature(
+        &mut self,
+        celsius: i8,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.temperature = Some(celsius);
+        if let Waveform::Mode(mode) = self.waveform
+            && TempRange::from_celsius(celsius) != self.lut_range
+        {
+            self.load_mode(mode).await?;
+        }
         Ok(())
     }
+
+    /// Read the on-chip sensor and apply it as in [`Self::set_temperature`].
+    pub async fn update_temperature(&mut self) -> Result<i8, DriverError<SPI, CS, DC, RST, BUSY>> {
+        let celsius = self.read_temperature().await?;
+        self.set_temperature(celsius).await?;
+        Ok(celsius)
+    }
+
+    /// Write one LUT register, zero-padded to the panel's register length.
+    async fn write_lut(
+        &mut self,
+        cmd: Command,
+        lut: &[u8],
+        len: usize,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.bus.write_cmd(cmd).await?;
+        self.bus.write_data(lut).await?;
+        self.send_zeros(len.saturating_sub(lut.len())).await
+    }
 }
@@ -1,4 +1,4 @@
-use super::LutSet;
+use super::{LutSet, table};
 
 const VOLTAGE_FRAME: [u8; 7] = [0x6, 0x3F, 0x3F, 0x11, 0x24, 0x7, 0x17];
 
@@ -36,3 +36,5 @@ pub const LUTS: LutSet = LutSet {
     wb: LUT_WB_OFFICIAL,
     bb: LUT_BB_OFFICIAL,
 };
+
+pub const TABLE: [LutSet; 3] = table(LUTS);
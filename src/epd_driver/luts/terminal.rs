@@ -1,4 +1,4 @@
-use super::LutSet;
+use super::{LutSet, table};
 
 const VOLTAGE_FRAME_FAST_TEXT_HC: [u8; 7] = [0x06, 0x24, 0x24, 0x08, 0x14, 0x04, 0x10];
 
@@ -35,3 +35,5 @@ pub const LUTS: LutSet = LutSet {
     wb: LUT_WB_FAST_TEXT_HC,
     bb: LUT_BB_FAST_TEXT_HC,
 };
+
+pub const TABLE: [LutSet; 3] = table(LUTS);
@@ -0,0 +1,470 @@
+//! Host-side mock of the SPI/GPIO lines used by [`EpdBus`].
+//!
+//! Every byte the driver clocks out is decoded into a [`Op`] trace so the
+//! command sequences of `init`, `display`, `set_mode`, ... can be compared
+//! against golden transcripts in `cargo test-host`. The BUSY line can be
+//! scripted to simulate slow or dead panels.
+
+use core::convert::Infallible;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::vec::Vec;
+
+use embassy_time::{Duration, Timer};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{ErrorType as DigitalErrorType, InputPin, OutputPin};
+use embedded_hal::spi::ErrorType as SpiErrorType;
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::{Operation, SpiBus, SpiDevice};
+
+use super::blocking::{BlockingSpi, PollingBusy};
+use super::bus::{DeviceSpi, EpdBus, NoCs};
+use super::command::Command;
+
+/// One recorded bus operation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Op {
+    /// RST was pulled low (hardware reset pulse).
+    Reset,
+    /// A command byte followed by every data byte sent until the next command.
+    Cmd(Command, Vec<u8>),
+    /// A command byte that does not decode into a known [`Command`].
+    Unknown(u8, Vec<u8>),
+    /// Data sent before any command was issued.
+    Data(Vec<u8>),
+    /// The driver waited for BUSY to go high.
+    WaitBusy,
+    /// Bytes clocked back from the controller.
+    Read(Vec<u8>),
+}
+
+impl Op {
+    /// Command and payload of this op, if it is a decoded command.
+    pub fn as_cmd(&self) -> Option<(Command, &[u8])> {
+        match self {
+            Op::Cmd(c, d) => Some((*c, d)),
+            _ => None,
+        }
+    }
+
+    /// Raw command byte and payload, for controllers whose commands do not
+    /// decode into [`Command`] (e.g. [`SsdCommand`](super::SsdCommand)).
+    pub fn raw(&self) -> Option<(u8, &[u8])> {
+        match self {
+            Op::Cmd(c, d) => Some(((*c).into(), d)),
+            Op::Unknown(b, d) => Some((*b, d)),
+            _ => None,
+        }
+    }
+}
+
+/// Behaviour of the BUSY line for one `wait_for_high` call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Busy {
+    /// Panel is idle, the wait returns immediately.
+    Ready,
+    /// Panel stays busy for the given time.
+    After(Duration),
+    /// Panel never becomes ready (disconnected or dead).
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Role {
+    Cs,
+    Dc,
+    Rst,
+    Led,
+}
+
+struct State {
+    ops: Vec<Op>,
+    cs_low: bool,
+    dc_high: bool,
+    rst_high: bool,
+    led_high: bool,
+    busy: VecDeque<Busy>,
+    reads: VecDeque<u8>,
+    delayed_ns: u64,
+}
+
+impl State {
+    fn push_bytes(&mut self, bytes: &[u8]) {
+        assert!(self.cs_low, "SPI transfer with CS deasserted");
+        if !self.dc_high {
+            for &b in bytes {
+                self.ops.push(match Command::try_from(b) {
+                    Ok(c) => Op::Cmd(c, Vec::new()),
+                    Err(raw) => Op::Unknown(raw, Vec::new()),
+                });
+            }
+            return;
+        }
+        match self.ops.last_mut() {
+            Some(Op::Cmd(_, d)) | Some(Op::Unknown(_, d)) | Some(Op::Data(d)) => {
+                d.extend_from_slice(bytes)
+            }
+            _ => self.ops.push(Op::Data(bytes.to_vec())),
+        }
+    }
+}
+
+/// Shared handle to the mocked hardware: hands out the pins and the SPI bus
+/// and gives access to the recorded trace.
+#[derive(Clone)]
+pub struct MockHw {
+    state: Rc<RefCell<State>>,
+}
+
+impl MockHw {
+    pub fn new() -> Self {
+        Self {
+            state: Rc::new(RefCell::new(State {
+                ops: Vec::new(),
+                cs_low: false,
+                dc_high: false,
+                rst_high: true,
+                led_high: false,
+                busy: VecDeque::new(),
+                reads: VecDeque::new(),
+                delayed_ns: 0,
+            })),
+        }
+    }
+
+    /// Build an [`EpdBus`] wired to this mock.
+    pub fn bus(&self) -> MockEpdBus {
+        EpdBus::new(
+            MockSpi(self.clone()),
+            self.pin(Role::Cs),
+            self.pin(Role::Dc),
+            self.pin(Role::Rst),
+            MockBusy(self.clone()),
+        )
+    }
+
+    /// Build a blocking-flavour [`EpdBus`] wired to this mock. BUSY is
+    /// polled through `InputPin`, which only honours [`Busy::Never`].
+    pub fn blocking_bus(&self) -> MockBlockingBus {
+        EpdBus::new_blocking(
+            MockSpi(self.clone()),
+            self.pin(Role::Cs),
+            self.pin(Role::Dc),
+            self.pin(Role::Rst),
+            MockBusy(self.clone()),
+            self.delay(),
+        )
+    }
+
+    /// Build an [`EpdBus`] over a mocked `SpiDevice`, which asserts CS
+    /// itself for the length of each transaction.
+    pub fn device_bus(&self) -> MockDeviceBus {
+        EpdBus::new_device(
+            MockDevice(MockSpi(self.clone())),
+            self.pin(Role::Dc),
+            self.pin(Role::Rst),
+            MockBusy(self.clone()),
+        )
+    }
+
+    /// The recording SPI bus on its own, for assembling an [`EpdBus`] with
+    /// some lines replaced.
+    pub fn spi(&self) -> MockSpi {
+        MockSpi(self.clone())
+    }
+
+    pub fn cs(&self) -> MockPin {
+        self.pin(Role::Cs)
+    }
+
+    pub fn dc(&self) -> MockPin {
+        self.pin(Role::Dc)
+    }
+
+    pub fn rst(&self) -> MockPin {
+        self.pin(Role::Rst)
+    }
+
+    pub fn busy(&self) -> MockBusy {
+        MockBusy(self.clone())
+    }
+
+    /// Status LED pin, as passed to `Epd800x480::new`.
+    pub fn led(&self) -> MockPin {
+        self.pin(Role::Led)
+    }
+
+    /// Blocking delay provider; it returns at once and only adds up the time
+    /// it was asked to wait (see [`delayed`](Self::delayed)).
+    pub fn delay(&self) -> MockDelay {
+        MockDelay(self.clone())
+    }
+
+    fn pin(&self, role: Role) -> MockPin {
+        MockPin {
+            hw: self.clone(),
+            role,
+        }
+    }
+
+    /// Queue the behaviour of the next BUSY wait. Once the queue is empty
+    /// the panel reports ready immediately.
+    pub fn script_busy(&self, b: Busy) {
+        self.state.borrow_mut().busy.push_back(b);
+    }
+
+    /// Queue bytes the controller returns on the next SPI reads. Reads
+    /// beyond the scripted bytes return `0x00`.
+    pub fn script_read(&self, bytes: &[u8]) {
+        self.state.borrow_mut().reads.extend(bytes);
+    }
+
+    /// Snapshot of everything recorded so far.
+    pub fn trace(&self) -> Vec<Op> {
+        self.state.borrow().ops.clone()
+    }
+
+    /// Return and clear the recorded trace.
+    pub fn take_trace(&self) -> Vec<Op> {
+        core::mem::take(&mut self.state.borrow_mut().ops)
+    }
+
+    /// Total time requested from [`MockDelay`] so far.
+    pub fn delayed(&self) -> Duration {
+        Duration::from_micros(self.state.borrow().delayed_ns / 1000)
+    }
+
+    /// Whether CS is currently asserted.
+    pub fn cs_is_low(&self) -> bool {
+        self.state.borrow().cs_low
+    }
+
+    /// Current level of the LED pin.
+    pub fn led_is_on(&self) -> bool {
+        self.state.borrow().led_high
+    }
+}
+
+impl Default for MockHw {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub type MockEpdBus = EpdBus<MockSpi, MockPin, MockPin, MockPin, MockBusy>;
+pub type MockBlockingBus =
+    EpdBus<BlockingSpi<MockSpi>, MockPin, MockPin, MockPin, PollingBusy<MockBusy, MockDelay>>;
+pub type MockDeviceBus = EpdBus<DeviceSpi<MockDevice>, NoCs, MockPin, MockPin, MockBusy>;
+
+/// Recording SPI bus; reads are served from [`MockHw::script_read`].
+pub struct MockSpi(MockHw);
+
+impl SpiErrorType for MockSpi {
+    type Error = Infallible;
+}
+
+impl MockSpi {
+    fn read_words(&mut self, words: &mut [u8]) {
+        let mut s = self.0.state.borrow_mut();
+        assert!(s.cs_low, "SPI transfer with CS deasserted");
+        for w in words.iter_mut() {
+            *w = s.reads.pop_front().unwrap_or(0);
+        }
+        s.ops.push(Op::Read(words.to_vec()));
+    }
+}
+
+impl SpiBus<u8> for MockSpi {
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.read_words(words);
+        Ok(())
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        self.0.state.borrow_mut().push_bytes(words);
+        Ok(())
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        self.write(write).await?;
+        read.fill(0);
+        Ok(())
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.write(words).await?;
+        words.fill(0);
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// The same recording bus for the blocking driver flavour.
+impl embedded_hal::spi::SpiBus<u8> for MockSpi {
+    fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.read_words(words);
+        Ok(())
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        self.0.state.borrow_mut().push_bytes(words);
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        embedded_hal::spi::SpiBus::write(self, write)?;
+        read.fill(0);
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        embedded_hal::spi::SpiBus::write(self, words)?;
+        words.fill(0);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// The recording bus behind a `SpiDevice` that owns chip select.
+pub struct MockDevice(MockSpi);
+
+impl SpiErrorType for MockDevice {
+    type Error = Infallible;
+}
+
+impl SpiDevice<u8> for MockDevice {
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        {
+            let mut s = self.0.0.state.borrow_mut();
+            assert!(!s.cs_low, "nested SPI transaction");
+            s.cs_low = true;
+        }
+        for op in operations {
+            match op {
+                Operation::Read(words) => self.0.read(words).await?,
+                Operation::Write(words) => self.0.write(words).await?,
+                Operation::Transfer(read, write) => self.0.transfer(read, write).await?,
+                Operation::TransferInPlace(words) => self.0.transfer_in_place(words).await?,
+                Operation::DelayNs(_) => {}
+            }
+        }
+        self.0.0.state.borrow_mut().cs_low = false;
+        Ok(())
+    }
+}
+
+/// Blocking `DelayNs` that only records the requested time.
+pub struct MockDelay(MockHw);
+
+impl DelayNs for MockDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.state.borrow_mut().delayed_ns += ns as u64;
+    }
+}
+
+/// CS, DC, RST or LED output.
+pub struct MockPin {
+    hw: MockHw,
+    role: Role,
+}
+
+impl DigitalErrorType for MockPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for MockPin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        let mut s = self.hw.state.borrow_mut();
+        match self.role {
+            Role::Cs => s.cs_low = true,
+            Role::Dc => s.dc_high = false,
+            Role::Rst => {
+                if s.rst_high {
+                    s.ops.push(Op::Reset);
+                }
+                s.rst_high = false;
+            }
+            Role::Led => s.led_high = false,
+        }
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let mut s = self.hw.state.borrow_mut();
+        match self.role {
+            Role::Cs => s.cs_low = false,
+            Role::Dc => s.dc_high = true,
+            Role::Rst => s.rst_high = true,
+            Role::Led => s.led_high = true,
+        }
+        Ok(())
+    }
+}
+
+/// Scriptable BUSY input. `wait_for_high` models the UC8179 (high = idle),
+/// `wait_for_low` the SSD16xx (low = idle); both follow the same script.
+pub struct MockBusy(MockHw);
+
+impl MockBusy {
+    fn next(&mut self) -> Busy {
+        let mut s = self.0.state.borrow_mut();
+        s.ops.push(Op::WaitBusy);
+        s.busy.pop_front().unwrap_or(Busy::Ready)
+    }
+}
+
+impl DigitalErrorType for MockBusy {
+    type Error = Infallible;
+}
+
+impl InputPin for MockBusy {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(!matches!(
+            self.0.state.borrow().busy.front(),
+            Some(Busy::Never)
+        ))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.is_high().map(|h| !h)
+    }
+}
+
+impl Wait for MockBusy {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        match self.next() {
+            Busy::Ready => Ok(()),
+            Busy::After(d) => {
+                Timer::after(d).await;
+                Ok(())
+            }
+            Busy::Never => core::future::pending().await,
+        }
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_high().await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_high().await
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_high().await
+    }
+}
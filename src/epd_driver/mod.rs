@@ -1,22 +1,46 @@
+pub mod blocking;
 mod bus;
 mod command;
+mod config;
 mod driver;
 mod error;
 mod luts;
+#[cfg(feature = "std")]
+pub mod mock;
+mod panel;
+mod policy;
+mod rect;
+#[cfg(feature = "std")]
+pub mod sim;
+mod ssd16xx;
+mod status;
+mod timeout;
+mod traits;
+mod tricolor;
 
-pub const WIDTH: usize = 800;
-pub const HEIGHT: usize = 480;
-pub const BUF_SIZE: usize = (WIDTH * HEIGHT) / 8;
+// Geometry of the default panel, [`Panel7in5V2`].
+pub const WIDTH: usize = Panel7in5V2::WIDTH;
+pub const HEIGHT: usize = Panel7in5V2::HEIGHT;
+pub const BUF_SIZE: usize = Panel7in5V2::BUF_SIZE;
+/// Size of a 2bpp (four-level gray) frame.
+pub const GRAY_BUF_SIZE: usize = Panel7in5V2::GRAY_BUF_SIZE;
 
-#[derive(Clone, Copy, Debug)]
-pub struct Rect {
-    pub x: usize,
-    pub y: usize,
-    pub w: usize,
-    pub h: usize,
-}
-
-pub use bus::EpdBus;
-pub use driver::Epd800x480;
-pub use error::DriverError;
-pub use luts::DisplayMode;
+pub use bus::{BusError, BusyLine, DeviceSpi, EpdBus, EpdBusError, NoCs};
+pub use command::{Command, SsdCommand};
+pub use config::{Border, EpdConfig, FrameRate};
+pub use driver::{Epd, Epd800x480};
+pub use error::{DriverError, EpdDriverError};
+pub use luts::analysis::{Level, LutError, PhaseGroup, phase_groups};
+pub use luts::format::LutParseError;
+pub use luts::{DisplayMode, LutSet, TempRange};
+pub use panel::{
+    Panel2in9V2, Panel4in2V2, Panel5in83V2, Panel7in5Hd, Panel7in5V2, PanelSpec, RamXAddress,
+    Ssd16xxPanel, Uc8179BwrPanel, Uc8179Panel,
+};
+pub use policy::RefreshPolicy;
+pub use rect::{Rect, RectError};
+pub use ssd16xx::Ssd16xx;
+pub use status::PanelStatus;
+pub use timeout::{BusyOp, Timeouts};
+pub use traits::EpdDriver;
+pub use tricolor::{Epd800x480Bwr, EpdBwr};
@@ -0,0 +1,210 @@
+//! Per-panel geometry and controller setup.
+//!
+//! [`PanelSpec`] holds what the console and framebuffer types need (the
+//! geometry); [`Uc8179Panel`], [`Uc8179BwrPanel`] and [`Ssd16xxPanel`] add
+//! the init sequence and register layout for the controller driving the
+//! glass. Supporting another
+//! panel means writing one more implementation of these traits.
+
+use super::command::{Command, SsdCommand};
+
+/// Resolution and partial-window alignment of one panel.
+pub trait PanelSpec {
+    /// Horizontal resolution (source lines) in pixels; a multiple of 8.
+    const WIDTH: usize;
+    /// Vertical resolution (gate lines) in pixels.
+    const HEIGHT: usize;
+    /// Size of a 1bpp frame.
+    const BUF_SIZE: usize = Self::WIDTH * Self::HEIGHT / 8;
+    /// Size of a 2bpp (four-level gray) frame.
+    const GRAY_BUF_SIZE: usize = Self::WIDTH * Self::HEIGHT / 4;
+
+    /// Partial windows must start and end on multiples of this many pixels
+    /// in x; a multiple of 8.
+    const X_ALIGN: usize = 8;
+}
+
+/// Init sequence and LUT register layout of a UC8179-family panel, driven by
+/// [`Epd`](super::Epd).
+pub trait Uc8179Panel: PanelSpec {
+    /// Register writes sent after the hardware reset, before `PowerOn`.
+    const POWER_SEQUENCE: &'static [(Command, &'static [u8])];
+    /// `PanelSetting` value selecting the OTP waveform. `set_mode` sets
+    /// bit 5 (REG) on top of it to switch to the register LUTs.
+    const PANEL_SETTING: u8;
+    /// Register writes sent after `PanelSetting` and `TRes`. The driver adds
+    /// the N2OCP bit to the `VcomAndDataInterval` entry when differential
+    /// updates are on.
+    const PANEL_SEQUENCE: &'static [(Command, &'static [u8])];
+
+    /// Bytes per LUT register. The 42-byte tables of a `LutSet` are
+    /// zero-padded up to this length.
+    const LUT_LEN: usize = 42;
+    /// Bytes in the VCOM LUT register, which is longer on some controllers.
+    const VCOM_LUT_LEN: usize = Self::LUT_LEN;
+}
+
+/// Init sequence of a black/white/red UC8179 panel, driven by
+/// [`EpdBwr`](super::EpdBwr) in KWR mode with the OTP waveform.
+///
+/// The red glass needs other supply voltages and timings than the
+/// black/white version of the same size, so this is a trait of its own.
+pub trait Uc8179BwrPanel: PanelSpec {
+    /// Register writes sent after the hardware reset, before `PowerOn`.
+    const KWR_POWER_SEQUENCE: &'static [(Command, &'static [u8])];
+    /// `PanelSetting` value; bit 4 (KWR) must be clear.
+    const KWR_PANEL_SETTING: u8;
+    /// Register writes sent after `PanelSetting` and `TRes`. The
+    /// `VcomAndDataInterval` entry must select DDX = 01, where a set bit is
+    /// white in DTM1 and red in DTM2.
+    const KWR_PANEL_SEQUENCE: &'static [(Command, &'static [u8])];
+}
+
+/// Waveshare 7.5" V2, 800x480 (UC8179).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Panel7in5V2;
+
+impl PanelSpec for Panel7in5V2 {
+    const WIDTH: usize = 800;
+    const HEIGHT: usize = 480;
+}
+
+impl Uc8179Panel for Panel7in5V2 {
+    const POWER_SEQUENCE: &'static [(Command, &'static [u8])] = &[
+        (Command::PowerSetting, &[0x17, 0x17, 0x3f, 0x3f, 0x11]),
+        (Command::VcomDc, &[0x24]),
+        (Command::Btst, &[0x27, 0x27, 0x2F, 0x17]),
+        // 50 Hz frame rate
+        (Command::Pll, &[0x06]),
+    ];
+    const PANEL_SETTING: u8 = 0x1F;
+    const PANEL_SEQUENCE: &'static [(Command, &'static [u8])] = &[
+        (Command::DualSPI, &[0x00]),
+        // BDV = 01, N2OCP = 0, DDX = 00; CDI = 0x07
+        (Command::VcomAndDataInterval, &[0x10, 0x07]),
+        (Command::TconSetting, &[0x22]),
+    ];
+}
+
+/// The B V2 (black/white/red) version of the 7.5" V2.
+impl Uc8179BwrPanel for Panel7in5V2 {
+    const KWR_POWER_SEQUENCE: &'static [(Command, &'static [u8])] = &[
+        // VGH=20V, VGL=-20V, VDH=15V, VDL=-15V
+        (Command::PowerSetting, &[0x07, 0x07, 0x3f, 0x3f]),
+        (Command::Btst, &[0x17, 0x17, 0x28, 0x17]),
+    ];
+    const KWR_PANEL_SETTING: u8 = 0x0F;
+    const KWR_PANEL_SEQUENCE: &'static [(Command, &'static [u8])] = &[
+        (Command::DualSPI, &[0x00]),
+        // DDX = 01: DTM1 bit 1 = white, DTM2 bit 1 = red
+        (Command::VcomAndDataInterval, &[0x11, 0x07]),
+        (Command::TconSetting, &[0x22]),
+        (Command::ResolutionSetting, &[0x00, 0x00, 0x00, 0x00]),
+    ];
+}
+
+/// Waveshare 5.83" V2, 648x480 (UC8179).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Panel5in83V2;
+
+impl PanelSpec for Panel5in83V2 {
+    const WIDTH: usize = 648;
+    const HEIGHT: usize = 480;
+}
+
+impl Uc8179Panel for Panel5in83V2 {
+    const POWER_SEQUENCE: &'static [(Command, &'static [u8])] = &[
+        // VGH=20V, VGL=-20V, VDH=15V, VDL=-15V
+        (Command::PowerSetting, &[0x07, 0x07, 0x3f, 0x3f]),
+    ];
+    const PANEL_SETTING: u8 = 0x1F;
+    const PANEL_SEQUENCE: &'static [(Command, &'static [u8])] = &[
+        (Command::DualSPI, &[0x00]),
+        (Command::VcomAndDataInterval, &[0x10, 0x07]),
+        (Command::TconSetting, &[0x22]),
+    ];
+}
+
+/// How an SSD16xx controller addresses the RAM X window and counter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RamXAddress {
+    /// One byte holding the column in units of 8 pixels (SSD1680/1681/1683).
+    Bytes,
+    /// Two bytes, little endian, holding the pixel column (SSD1677).
+    Pixels,
+}
+
+/// Init sequence and RAM layout of a Solomon SSD16xx panel, driven by
+/// [`Ssd16xx`](super::Ssd16xx). Only the OTP waveforms are used.
+pub trait Ssd16xxPanel: PanelSpec {
+    const X_ADDRESS: RamXAddress;
+    /// Register writes sent after the software reset. The driver sets the
+    /// data entry mode and RAM window itself.
+    const INIT_SEQUENCE: &'static [(SsdCommand, &'static [u8])];
+    /// `DisplayUpdateControl2` value for a full refresh.
+    const FULL_UPDATE: u8 = 0xF7;
+    /// `DisplayUpdateControl2` value for a partial refresh (display mode 2,
+    /// which drives only pixels that differ between the two RAMs).
+    const PARTIAL_UPDATE: u8 = 0xFF;
+}
+
+/// Waveshare 4.2" V2, 400x300 (SSD1683).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Panel4in2V2;
+
+impl PanelSpec for Panel4in2V2 {
+    const WIDTH: usize = 400;
+    const HEIGHT: usize = 300;
+}
+
+impl Ssd16xxPanel for Panel4in2V2 {
+    const X_ADDRESS: RamXAddress = RamXAddress::Bytes;
+    const INIT_SEQUENCE: &'static [(SsdCommand, &'static [u8])] = &[
+        // Use the RED RAM as the previous image
+        (SsdCommand::DisplayUpdateControl1, &[0x00, 0x00]),
+        (SsdCommand::BorderWaveform, &[0x05]),
+    ];
+}
+
+/// Waveshare 2.9" V2, 128x296 (SSD1680).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Panel2in9V2;
+
+impl PanelSpec for Panel2in9V2 {
+    const WIDTH: usize = 128;
+    const HEIGHT: usize = 296;
+}
+
+impl Ssd16xxPanel for Panel2in9V2 {
+    const X_ADDRESS: RamXAddress = RamXAddress::Bytes;
+    const INIT_SEQUENCE: &'static [(SsdCommand, &'static [u8])] = &[
+        // MUX = 296 gate lines
+        (SsdCommand::DriverOutputControl, &[0x27, 0x01, 0x00]),
+        (SsdCommand::DisplayUpdateControl1, &[0x00, 0x80]),
+        (SsdCommand::BorderWaveform, &[0x05]),
+    ];
+}
+
+/// Waveshare 7.5" HD, 880x528 (SSD1677).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Panel7in5Hd;
+
+impl PanelSpec for Panel7in5Hd {
+    const WIDTH: usize = 880;
+    const HEIGHT: usize = 528;
+}
+
+impl Ssd16xxPanel for Panel7in5Hd {
+    const X_ADDRESS: RamXAddress = RamXAddress::Pixels;
+    const INIT_SEQUENCE: &'static [(SsdCommand, &'static [u8])] = &[
+        (
+            SsdCommand::BoosterSoftStart,
+            &[0xAE, 0xC7, 0xC3, 0xC0, 0x40],
+        ),
+        // MUX = 528 gate lines
+        (SsdCommand::DriverOutputControl, &[0xAF, 0x02, 0x00]),
+        (SsdCommand::BorderWaveform, &[0x01]),
+        // Internal temperature sensor
+        (SsdCommand::TempSensor, &[0x80]),
+    ];
+}
@@ -0,0 +1,54 @@
+use embassy_time::{Duration, Instant};
+
+use super::Rect;
+
+/// Limits after which the driver inserts a full `Official` refresh to clear
+/// the ghosting that fast/terminal partial updates leave behind. `None`
+/// disables a limit; the default disables all of them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RefreshPolicy {
+    /// Partial updates allowed between cleaning refreshes.
+    pub max_partials: Option<u32>,
+    /// Sum of partially refreshed pixels (w * h per update) allowed between
+    /// cleaning refreshes.
+    pub max_partial_area: Option<usize>,
+    /// Longest time since the last cleaning refresh before the next partial
+    /// update triggers one. Needs a BUSY line with a clock (ignored with
+    /// `PollingBusy`).
+    pub max_age: Option<Duration>,
+}
+
+/// Updates accumulated since the last cleaning refresh.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RefreshStats {
+    partials: u32,
+    area: usize,
+    since: Option<Instant>,
+}
+
+impl RefreshStats {
+    pub(crate) fn new(now: Option<Instant>) -> Self {
+        Self {
+            partials: 0,
+            area: 0,
+            since: now,
+        }
+    }
+
+    pub(crate) fn record(&mut self, r: Rect) {
+        self.partials = self.partials.saturating_add(1);
+        self.area = self.area.saturating_add(r.w * r.h);
+    }
+
+    pub(crate) fn exceeds(&self, policy: &RefreshPolicy, now: Option<Instant>) -> bool {
+        let age = match (self.since, now) {
+            (Some(since), Some(now)) => Some(now.saturating_duration_since(since)),
+            _ => None,
+        };
+        policy.max_partials.is_some_and(|m| self.partials >= m)
+            || policy.max_partial_area.is_some_and(|m| self.area >= m)
+            || policy
+                .max_age
+                .is_some_and(|m| age.is_some_and(|age| age >= m))
+    }
+}
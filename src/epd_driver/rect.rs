@@ -0,0 +1,54 @@
+use super::panel::PanelSpec;
+
+/// A window of the panel in pixels, for partial refreshes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+/// Why a [`Rect`] cannot be refreshed on a panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RectError {
+    /// `w` or `h` is zero.
+    Empty,
+    /// The window reaches past the panel's right or bottom edge.
+    OutOfBounds,
+    /// `x` or `w` is not a multiple of the panel's `X_ALIGN`.
+    Misaligned,
+}
+
+impl Rect {
+    /// A window of panel `P`, checked the way the partial refreshes check
+    /// it.
+    pub fn new<P: PanelSpec>(x: usize, y: usize, w: usize, h: usize) -> Result<Self, RectError> {
+        let r = Rect { x, y, w, h };
+        r.check::<P>()?;
+        Ok(r)
+    }
+
+    /// Check that the window is non-empty, on `P`'s screen and aligned to
+    /// its `X_ALIGN`.
+    pub fn check<P: PanelSpec>(&self) -> Result<(), RectError> {
+        if self.w == 0 || self.h == 0 {
+            return Err(RectError::Empty);
+        }
+        let fits = |start: usize, len: usize, max: usize| {
+            start.checked_add(len).is_some_and(|end| end <= max)
+        };
+        if !fits(self.x, self.w, P::WIDTH) || !fits(self.y, self.h, P::HEIGHT) {
+            return Err(RectError::OutOfBounds);
+        }
+        if !self.x.is_multiple_of(P::X_ALIGN) || !self.w.is_multiple_of(P::X_ALIGN) {
+            return Err(RectError::Misaligned);
+        }
+        Ok(())
+    }
+
+    /// Bytes of a packed 1bpp buffer holding just this window.
+    pub const fn buf_len(&self) -> usize {
+        self.w / 8 * self.h
+    }
+}
@@ -0,0 +1,289 @@
+//! Host-side UC8179 panel simulator.
+//!
+//! Consumes the [`Op`] trace recorded by [`mock`](super::mock) and keeps the
+//! controller's "old" (DTM1) and "new" (DTM2) image planes, honouring the
+//! resolution, partial window and data polarity registers. Every
+//! `DisplayRefresh` produces a [`Frame`] of what the panel would show, which
+//! can be written out as PBM or PNG.
+
+use std::io::{self, Write};
+use std::vec;
+use std::vec::Vec;
+
+use super::command::Command;
+use super::mock::Op;
+use super::{HEIGHT, PanelSpec, Rect, WIDTH};
+
+/// A 1bpp snapshot of the panel, row-major, MSB-first, `1` = black.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0; width.div_ceil(8) * height],
+        }
+    }
+
+    fn row_bytes(&self) -> usize {
+        self.width.div_ceil(8)
+    }
+
+    /// `true` if the pixel at (x, y) is black.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        let b = self.data[y * self.row_bytes() + x / 8];
+        b & (0x80 >> (x % 8)) != 0
+    }
+
+    /// Write the frame as a binary PBM (P4) image.
+    pub fn write_pbm<W: Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "P4\n{} {}\n", self.width, self.height)?;
+        w.write_all(&self.data)
+    }
+
+    /// Write the frame as a 1-bit grayscale PNG.
+    pub fn write_png<W: Write>(&self, w: W) -> io::Result<()> {
+        let mut enc = png::Encoder::new(w, self.width as u32, self.height as u32);
+        enc.set_color(png::ColorType::Grayscale);
+        enc.set_depth(png::BitDepth::One);
+        let mut writer = enc.write_header().map_err(io::Error::other)?;
+        // PNG grayscale uses 1 = white, the frame uses 1 = black.
+        let inverted: Vec<u8> = self.data.iter().map(|b| !b).collect();
+        writer
+            .write_image_data(&inverted)
+            .map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+}
+
+/// Emulated UC8179-style controller.
+pub struct PanelSim {
+    width: usize,
+    height: usize,
+    old: Vec<u8>,
+    new: Vec<u8>,
+    screen: Frame,
+    red: Frame,
+    panel_setting: u8,
+    invert: bool,
+    copy_new_to_old: bool,
+    partial: bool,
+    window: Rect,
+    frames: Vec<Frame>,
+    last_driven: usize,
+}
+
+impl PanelSim {
+    /// A simulator for the 800x480 panel, blank (white) and in full-screen mode.
+    pub fn new() -> Self {
+        Self::with_size(WIDTH, HEIGHT)
+    }
+
+    /// A simulator sized for the panel `P`.
+    pub fn for_panel<P: PanelSpec>() -> Self {
+        Self::with_size(P::WIDTH, P::HEIGHT)
+    }
+
+    pub fn with_size(width: usize, height: usize) -> Self {
+        let bytes = width.div_ceil(8) * height;
+        Self {
+            width,
+            height,
+            old: vec![0; bytes],
+            new: vec![0; bytes],
+            screen: Frame::new(width, height),
+            red: Frame::new(width, height),
+            panel_setting: 0x1F,
+            invert: false,
+            copy_new_to_old: false,
+            partial: false,
+            window: Rect {
+                x: 0,
+                y: 0,
+                w: width,
+                h: height,
+            },
+            frames: Vec::new(),
+            last_driven: 0,
+        }
+    }
+
+    /// Apply a sequence of recorded bus operations.
+    pub fn feed(&mut self, ops: &[Op]) {
+        for op in ops {
+            self.apply(op);
+        }
+    }
+
+    /// Apply a single recorded bus operation.
+    pub fn apply(&mut self, op: &Op) {
+        let Op::Cmd(cmd, data) = op else {
+            return;
+        };
+        match cmd {
+            Command::PanelSetting => {
+                if let Some(&b) = data.first() {
+                    self.panel_setting = b;
+                }
+            }
+            Command::TRes if data.len() >= 4 => {
+                let w = u16::from_be_bytes([data[0], data[1]]) as usize;
+                let h = u16::from_be_bytes([data[2], data[3]]) as usize;
+                if (w, h) != (self.width, self.height) {
+                    *self = Self {
+                        panel_setting: self.panel_setting,
+                        invert: self.invert,
+                        copy_new_to_old: self.copy_new_to_old,
+                        ..Self::with_size(w, h)
+                    };
+                }
+            }
+            Command::VcomAndDataInterval => {
+                // DDX[0] flips the meaning of a data bit in KW mode; N2OCP
+                // copies the new plane into the old one after each refresh.
+                if let Some(&b) = data.first() {
+                    self.invert = b & 0x01 != 0;
+                    self.copy_new_to_old = b & 0x08 != 0;
+                }
+            }
+            Command::PartialIn => self.partial = true,
+            Command::PartialOut => self.partial = false,
+            Command::PartialWindow if data.len() >= 8 => {
+                let be = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]) as usize;
+                let x0 = be(0) & !7;
+                let x1 = (be(2) | 7).min(self.width - 1);
+                let y0 = be(4);
+                let y1 = be(6).min(self.height - 1);
+                self.window = Rect {
+                    x: x0,
+                    y: y0,
+                    w: (x1 + 1).saturating_sub(x0),
+                    h: (y1 + 1).saturating_sub(y0),
+                };
+            }
+            Command::DataStartTransmission1 => {
+                let (r, row) = (self.active_window(), self.row_bytes());
+                write_window(&mut self.old, row, r, data);
+            }
+            Command::DataStartTransmission2 => {
+                let (r, row) = (self.active_window(), self.row_bytes());
+                write_window(&mut self.new, row, r, data);
+            }
+            Command::DisplayRefresh => self.refresh(),
+            _ => {}
+        }
+    }
+
+    fn row_bytes(&self) -> usize {
+        self.width.div_ceil(8)
+    }
+
+    fn active_window(&self) -> Rect {
+        if self.partial {
+            self.window
+        } else {
+            Rect {
+                x: 0,
+                y: 0,
+                w: self.width,
+                h: self.height,
+            }
+        }
+    }
+
+    fn refresh(&mut self) {
+        let r = self.active_window();
+        let row = self.row_bytes();
+        let mask = if self.invert { 0xFF } else { 0x00 };
+        // PanelSetting bit 4 clear: KWR mode, DTM1 = black/white and
+        // DTM2 = red (DDX[0] = 1 means 1 = white resp. 1 = red).
+        let kwr = self.panel_setting & 0x10 == 0;
+        self.last_driven = 0;
+        for y in r.y..r.y + r.h {
+            for bx in r.x / 8..(r.x + r.w).div_ceil(8) {
+                let i = y * row + bx;
+                if kwr {
+                    self.red.data[i] = self.new[i] ^ !mask;
+                    self.screen.data[i] = (self.old[i] ^ mask) & !self.red.data[i];
+                    continue;
+                }
+                self.last_driven += (self.old[i] ^ self.new[i]).count_ones() as usize;
+                self.screen.data[i] = self.new[i] ^ mask;
+                if self.copy_new_to_old {
+                    self.old[i] = self.new[i];
+                }
+            }
+        }
+        self.frames.push(self.screen.clone());
+    }
+
+    /// Pixels whose old and new plane bits differed in the last refresh,
+    /// i.e. the ones that got the BW/WB rather than the WW/BB LUTs. The
+    /// simulator does not model the LUTs themselves.
+    pub fn last_driven(&self) -> usize {
+        self.last_driven
+    }
+
+    /// What the panel currently shows.
+    pub fn screen(&self) -> &Frame {
+        &self.screen
+    }
+
+    /// Red pixels currently shown (KWR mode only; `1` = red).
+    pub fn red(&self) -> &Frame {
+        &self.red
+    }
+
+    /// Every frame shown so far, one per `DisplayRefresh`.
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Raw contents of the DTM1 ("old") plane.
+    pub fn old_plane(&self) -> &[u8] {
+        &self.old
+    }
+
+    /// Raw contents of the DTM2 ("new") plane.
+    pub fn new_plane(&self) -> &[u8] {
+        &self.new
+    }
+
+    /// Last value written to `PanelSetting`.
+    pub fn panel_setting(&self) -> u8 {
+        self.panel_setting
+    }
+
+    /// Whether the controller is currently in partial mode.
+    pub fn in_partial(&self) -> bool {
+        self.partial
+    }
+}
+
+impl Default for PanelSim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stream `data` row-major into the byte window `r` of a plane.
+fn write_window(plane: &mut [u8], row_bytes: usize, r: Rect, data: &[u8]) {
+    let bx0 = r.x / 8;
+    let w_bytes = (r.x + r.w).div_ceil(8) - bx0;
+    if w_bytes == 0 {
+        return;
+    }
+    for (i, &b) in data.iter().enumerate() {
+        let y = r.y + i / w_bytes;
+        if y >= r.y + r.h {
+            break;
+        }
+        plane[y * row_bytes + bx0 + i % w_bytes] = b;
+    }
+}
@@ -0,0 +1,304 @@
+use core::marker::PhantomData;
+
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::spi::SpiBus;
+
+// Error Types
+use embedded_hal::spi::ErrorType as SpiErrorType;
+
+use super::Rect;
+use super::bus::{BusyLine, CHUNK, EpdBus};
+use super::command::SsdCommand;
+use super::error::{DriverError, EpdDriverError};
+use super::panel::{RamXAddress, Ssd16xxPanel};
+use super::timeout::{BusyOp, Timeouts};
+use super::traits::EpdDriver;
+
+/// Driver for panels with a Solomon SSD16xx controller (SSD1677, SSD1680,
+/// SSD1681, SSD1683).
+///
+/// The BW RAM (0x24) holds the new frame and the RED RAM (0x26) the
+/// previous one, which the partial waveform diffs against; both are kept in
+/// sync after every update. BUSY is active high on these controllers.
+pub struct Ssd16xx<P, SPI, CS, DC, RST, BUSY, LED> {
+    pub bus: EpdBus<SPI, CS, DC, RST, BUSY>,
+    led: LED,
+    timeouts: Timeouts,
+    panel: PhantomData<P>,
+}
+
+impl<P, SPI, CS, DC, RST, BUSY, LED> Ssd16xx<P, SPI, CS, DC, RST, BUSY, LED>
+where
+    P: Ssd16xxPanel,
+    SPI: SpiBus<u8> + SpiErrorType,
+    CS: OutputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    BUSY: BusyLine,
+    LED: OutputPin,
+{
+    pub fn new(bus: EpdBus<SPI, CS, DC, RST, BUSY>, led: LED) -> Self {
+        Self {
+            bus,
+            led,
+            timeouts: Timeouts::default(),
+            panel: PhantomData,
+        }
+    }
+
+    /// Set the upper bound for each kind of BUSY wait.
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    pub async fn hw_reset(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.bus
+            .reset(20, 2, 20)
+            .await
+            .map_err(EpdDriverError::from)?;
+        self.wait_busy(BusyOp::Reset).await
+    }
+
+    pub async fn wait_ready(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.wait_busy(BusyOp::Refresh).await
+    }
+
+    async fn wait_busy(&mut self, op: BusyOp) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.bus
+            .wait_idle_low(&mut self.led, &self.timeouts, op)
+            .await
+    }
+
+    pub async fn init(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.hw_reset().await?;
+        self.bus.write_cmd(SsdCommand::SwReset).await?;
+        self.wait_busy(BusyOp::Reset).await?;
+
+        for &(cmd, data) in P::INIT_SEQUENCE {
+            self.bus.write_cmd(cmd).await?;
+            self.bus.write_data(data).await?;
+        }
+
+        // X increment, Y increment, counter moves along X first
+        self.bus.write_cmd(SsdCommand::DataEntryMode).await?;
+        self.bus.write_data(&[0x03]).await?;
+        self.set_window(full_screen::<P>()).await
+    }
+
+    pub async fn display(&mut self, buf: &[u8]) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        if buf.len() != P::BUF_SIZE {
+            return Err(EpdDriverError::BadBufferLen {
+                expected: P::BUF_SIZE,
+                got: buf.len(),
+            });
+        }
+        self.wait_ready().await?;
+        for ram in [SsdCommand::WriteRamBw, SsdCommand::WriteRamRed] {
+            self.set_window(full_screen::<P>()).await?;
+            self.bus.write_cmd(ram).await?;
+            self.send_inverted(buf).await?;
+        }
+        self.update(P::FULL_UPDATE).await
+    }
+
+    /// Partial refresh of `r` with `buf` holding just that window, checked
+    /// like [`Epd::display_partial`](super::Epd::display_partial).
+    pub async fn display_partial(
+        &mut self,
+        buf: &[u8],
+        r: Rect,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        r.check::<P>()?;
+        if buf.len() != r.buf_len() {
+            return Err(EpdDriverError::BadBufferLen {
+                expected: r.buf_len(),
+                got: buf.len(),
+            });
+        }
+        self.wait_ready().await?;
+        self.set_window(r).await?;
+        self.bus.write_cmd(SsdCommand::WriteRamBw).await?;
+        self.send_inverted(buf).await?;
+        self.update(P::PARTIAL_UPDATE).await?;
+        // The new image becomes the base of the next partial update.
+        self.set_window(r).await?;
+        self.bus.write_cmd(SsdCommand::WriteRamRed).await?;
+        self.send_inverted(buf).await
+    }
+
+    /// Partial refresh of `r`, taking its pixels straight from the
+    /// full-screen buffer `fb` one row at a time.
+    /// `r.x` and `r.w` must be multiples of 8.
+    ///
+    /// Windows that are empty, off screen or unaligned are rejected before
+    /// anything is sent.
+    pub async fn display_region(
+        &mut self,
+        fb: &[u8],
+        r: Rect,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        if fb.len() != P::BUF_SIZE {
+            return Err(EpdDriverError::BadBufferLen {
+                expected: P::BUF_SIZE,
+                got: fb.len(),
+            });
+        }
+        r.check::<P>()?;
+        self.wait_ready().await?;
+        self.write_region(SsdCommand::WriteRamBw, fb, r).await?;
+        self.update(P::PARTIAL_UPDATE).await?;
+        self.write_region(SsdCommand::WriteRamRed, fb, r).await
+    }
+
+    pub async fn clear(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.wait_ready().await?;
+        for ram in [SsdCommand::WriteRamBw, SsdCommand::WriteRamRed] {
+            self.set_window(full_screen::<P>()).await?;
+            self.bus.write_cmd(ram).await?;
+            // 1 = white in the controller RAM
+            self.bus.write_fill(0xFF, P::BUF_SIZE).await?;
+        }
+        self.update(P::FULL_UPDATE).await
+    }
+
+    pub async fn sleep(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.wait_ready().await?;
+        self.bus.write_cmd(SsdCommand::DeepSleep).await?;
+        self.bus.write_data(&[0x01]).await?;
+        Ok(())
+    }
+
+    /// Run the display update sequence selected by `ctrl` and wait for it.
+    async fn update(&mut self, ctrl: u8) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.bus
+            .write_cmd(SsdCommand::DisplayUpdateControl2)
+            .await?;
+        self.bus.write_data(&[ctrl]).await?;
+        self.bus.write_cmd(SsdCommand::MasterActivation).await?;
+        self.bus.delay_ms(10).await;
+        self.wait_ready().await
+    }
+
+    /// Set the RAM window to `r` and move the address counter to its origin.
+    async fn set_window(&mut self, r: Rect) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        let x_end = r.x + r.w - 1;
+        let y_end = r.y + r.h - 1;
+        self.bus.write_cmd(SsdCommand::RamXRange).await?;
+        match P::X_ADDRESS {
+            RamXAddress::Bytes => {
+                self.bus
+                    .write_data(&[(r.x / 8) as u8, (x_end / 8) as u8])
+                    .await?
+            }
+            RamXAddress::Pixels => {
+                self.bus
+                    .write_data(&[lb(r.x), hb(r.x), lb(x_end), hb(x_end)])
+                    .await?
+            }
+        }
+        self.bus.write_cmd(SsdCommand::RamYRange).await?;
+        self.bus
+            .write_data(&[lb(r.y), hb(r.y), lb(y_end), hb(y_end)])
+            .await?;
+        self.bus.write_cmd(SsdCommand::RamXCounter).await?;
+        match P::X_ADDRESS {
+            RamXAddress::Bytes => self.bus.write_data(&[(r.x / 8) as u8]).await?,
+            RamXAddress::Pixels => self.bus.write_data(&[lb(r.x), hb(r.x)]).await?,
+        }
+        self.bus.write_cmd(SsdCommand::RamYCounter).await?;
+        self.bus.write_data(&[lb(r.y), hb(r.y)]).await?;
+        Ok(())
+    }
+
+    /// Write the window `r` of the full frame `fb` into one RAM.
+    async fn write_region(
+        &mut self,
+        ram: SsdCommand,
+        fb: &[u8],
+        r: Rect,
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.set_window(r).await?;
+        self.bus.write_cmd(ram).await?;
+        let row_bytes = P::WIDTH / 8;
+        for y in r.y..r.y + r.h {
+            let start = y * row_bytes + r.x / 8;
+            self.send_inverted(&fb[start..start + r.w / 8]).await?;
+        }
+        Ok(())
+    }
+
+    /// The controller RAM uses 1 = white, the crate 1 = black.
+    async fn send_inverted(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        let mut chunk = [0u8; CHUNK];
+        for src in data.chunks(CHUNK) {
+            for (d, s) in chunk.iter_mut().zip(src) {
+                *d = !s;
+            }
+            self.bus.write_data(&chunk[..src.len()]).await?;
+        }
+        Ok(())
+    }
+}
+
+impl<P, SPI, CS, DC, RST, BUSY, LED> EpdDriver for Ssd16xx<P, SPI, CS, DC, RST, BUSY, LED>
+where
+    P: Ssd16xxPanel,
+    SPI: SpiBus<u8> + SpiErrorType,
+    CS: OutputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    BUSY: BusyLine,
+    LED: OutputPin,
+{
+    type Panel = P;
+    type Error = DriverError<SPI, CS, DC, RST, BUSY>;
+
+    async fn init(&mut self) -> Result<(), Self::Error> {
+        Ssd16xx::init(self).await
+    }
+
+    async fn display(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        Ssd16xx::display(self, buf).await
+    }
+
+    async fn display_partial(&mut self, buf: &[u8], r: Rect) -> Result<(), Self::Error> {
+        Ssd16xx::display_partial(self, buf, r).await
+    }
+
+    async fn display_region(&mut self, fb: &[u8], r: Rect) -> Result<(), Self::Error> {
+        Ssd16xx::display_region(self, fb, r).await
+    }
+
+    async fn clear(&mut self) -> Result<(), Self::Error> {
+        Ssd16xx::clear(self).await
+    }
+
+    async fn sleep(&mut self) -> Result<(), Self::Error> {
+        Ssd16xx::sleep(self).await
+    }
+}
+
+fn full_screen<P: Ssd16xxPanel>() -> Rect {
+    Rect {
+        x: 0,
+        y: 0,
+        w: P::WIDTH,
+        h: P::HEIGHT,
+    }
+}
+
+#[inline(always)]
+fn hb(x: usize) -> u8 {
+    (x >> 8) as u8
+}
+#[inline(always)]
+fn lb(x: usize) -> u8 {
+    (x & 0xFF) as u8
+}
@@ -0,0 +1,36 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Controller status flags returned by `GetStatus` (0x71).
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct PanelStatus: u8 {
+        /// Controller idle (mirrors the BUSY pin, active low busy).
+        const BUSY_N = 1 << 0;
+        /// Charge pump is off.
+        const POWER_OFF = 1 << 1;
+        /// Charge pump is on.
+        const POWER_ON = 1 << 2;
+        /// Image data received since the last refresh.
+        const DATA = 1 << 3;
+        /// External temperature-sensor I2C master idle.
+        const I2C_BUSY_N = 1 << 4;
+        /// External temperature-sensor I2C transaction failed.
+        const I2C_ERR = 1 << 5;
+        /// Partial mode enabled.
+        const PARTIAL = 1 << 6;
+    }
+}
+
+impl PanelStatus {
+    pub fn is_busy(&self) -> bool {
+        !self.contains(Self::BUSY_N)
+    }
+
+    pub fn is_powered(&self) -> bool {
+        self.contains(Self::POWER_ON)
+    }
+
+    pub fn i2c_error(&self) -> bool {
+        self.contains(Self::I2C_ERR)
+    }
+}
@@ -0,0 +1,48 @@
+use embassy_time::Duration;
+
+/// Operation the driver was waiting on when BUSY stayed low.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusyOp {
+    /// Controller start-up after the RST pulse.
+    Reset,
+    /// Booster / charge-pump start after `PowerOn`.
+    PowerOn,
+    /// Waveform playback after `DisplayRefresh` (also used for the idle check
+    /// before sending a new frame).
+    Refresh,
+    /// Charge-pump shutdown after `PowerOff`.
+    PowerOff,
+}
+
+/// Upper bounds for every BUSY wait, so a disconnected or dead panel
+/// surfaces as `EpdDriverError::Timeout` instead of hanging the executor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timeouts {
+    pub reset: Duration,
+    pub power_on: Duration,
+    pub refresh: Duration,
+    pub power_off: Duration,
+}
+
+impl Timeouts {
+    pub fn for_op(&self, op: BusyOp) -> Duration {
+        match op {
+            BusyOp::Reset => self.reset,
+            BusyOp::PowerOn => self.power_on,
+            BusyOp::Refresh => self.refresh,
+            BusyOp::PowerOff => self.power_off,
+        }
+    }
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            reset: Duration::from_millis(1_000),
+            power_on: Duration::from_millis(2_000),
+            // The official full-refresh waveform takes ~4 s at 50 Hz.
+            refresh: Duration::from_millis(10_000),
+            power_off: Duration::from_millis(2_000),
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use super::Rect;
+use super::panel::PanelSpec;
+
+/// What the console and framebuffer code needs from a panel driver,
+/// implemented by both controller backends ([`Epd`](super::Epd) for the
+/// UC8179 family and [`Ssd16xx`](super::Ssd16xx)).
+///
+/// Buffers are 1bpp, row-major, MSB-first, `1` = black, sized for
+/// `Self::Panel`.
+#[allow(async_fn_in_trait)]
+pub trait EpdDriver {
+    type Panel: PanelSpec;
+    type Error;
+
+    async fn init(&mut self) -> Result<(), Self::Error>;
+    /// Full refresh with a whole frame.
+    async fn display(&mut self, buf: &[u8]) -> Result<(), Self::Error>;
+    /// Partial refresh of `r` with `buf` holding just that window.
+    async fn display_partial(&mut self, buf: &[u8], r: Rect) -> Result<(), Self::Error>;
+    /// Partial refresh of `r`, taking its pixels from the full frame `fb`.
+    async fn display_region(&mut self, fb: &[u8], r: Rect) -> Result<(), Self::Error>;
+    /// Blank the panel to white.
+    async fn clear(&mut self) -> Result<(), Self::Error>;
+    async fn sleep(&mut self) -> Result<(), Self::Error>;
+}
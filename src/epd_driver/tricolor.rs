@@ -0,0 +1,157 @@
+use core::marker::PhantomData;
+
+use embassy_time::Duration;
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::spi::SpiBus;
+
+// Error Types
+use embedded_hal::spi::ErrorType as SpiErrorType;
+
+use super::bus::{BusyLine, CHUNK, EpdBus};
+use super::command::Command;
+use super::error::{DriverError, EpdDriverError};
+use super::panel::{Panel7in5V2, Uc8179BwrPanel};
+use super::timeout::{BusyOp, Timeouts};
+
+/// Driver for the black/white/red (B V2) version of the 7.5" 800x480 panel.
+pub type Epd800x480Bwr<SPI, CS, DC, RST, BUSY, LED> =
+    EpdBwr<Panel7in5V2, SPI, CS, DC, RST, BUSY, LED>;
+
+/// Driver for black/white/red UC8179 panels, with the geometry and init
+/// sequence taken from `P`.
+///
+/// The controller runs in KWR mode: DTM1 carries the black plane and DTM2
+/// the red plane. Both planes passed to [`display`](Self::display) use the
+/// crate-wide convention of `1` = ink (black resp. red); red wins where both
+/// are set. Refreshes always use the OTP waveform and take ~15 s.
+pub struct EpdBwr<P, SPI, CS, DC, RST, BUSY, LED> {
+    pub bus: EpdBus<SPI, CS, DC, RST, BUSY>,
+    led: LED,
+    timeouts: Timeouts,
+    panel: PhantomData<P>,
+}
+
+impl<P, SPI, CS, DC, RST, BUSY, LED> EpdBwr<P, SPI, CS, DC, RST, BUSY, LED>
+where
+    P: Uc8179BwrPanel,
+    SPI: SpiBus<u8> + SpiErrorType,
+    CS: OutputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    BUSY: BusyLine,
+    LED: OutputPin,
+{
+    pub fn new(bus: EpdBus<SPI, CS, DC, RST, BUSY>, led: LED) -> Self {
+        Self {
+            bus,
+            led,
+            timeouts: Timeouts {
+                refresh: Duration::from_millis(30_000),
+                ..Timeouts::default()
+            },
+            panel: PhantomData,
+        }
+    }
+
+    /// Set the upper bound for each kind of BUSY wait.
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    pub async fn hw_reset(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.bus.hw_reset(&mut self.led, &self.timeouts).await
+    }
+
+    pub async fn wait_ready(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.wait_busy(BusyOp::Refresh).await
+    }
+
+    async fn wait_busy(&mut self, op: BusyOp) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.bus.wait_idle(&mut self.led, &self.timeouts, op).await
+    }
+
+    pub async fn init(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.hw_reset().await?;
+
+        for (cmd, data) in P::KWR_POWER_SEQUENCE {
+            self.bus.write_cmd(*cmd).await?;
+            self.bus.write_data(data).await?;
+        }
+
+        // Power On
+        self.bus.write_cmd(Command::PowerOn).await?;
+        self.wait_busy(BusyOp::PowerOn).await?;
+
+        // KWR mode, OTP LUT
+        self.bus.write_cmd(Command::PanelSetting).await?;
+        self.bus.write_data(&[P::KWR_PANEL_SETTING]).await?;
+        self.bus.write_cmd(Command::TRes).await?;
+        self.bus
+            .write_data(&[
+                (P::WIDTH >> 8) as u8,
+                (P::WIDTH & 0xFF) as u8,
+                (P::HEIGHT >> 8) as u8,
+                (P::HEIGHT & 0xFF) as u8,
+            ])
+            .await?;
+        for (cmd, data) in P::KWR_PANEL_SEQUENCE {
+            self.bus.write_cmd(*cmd).await?;
+            self.bus.write_data(data).await?;
+        }
+        Ok(())
+    }
+
+    /// Show a black plane and a red plane, each `P::BUF_SIZE` bytes.
+    pub async fn display(
+        &mut self,
+        black: &[u8],
+        red: &[u8],
+    ) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        for buf in [black, red] {
+            if buf.len() != P::BUF_SIZE {
+                return Err(EpdDriverError::BadBufferLen {
+                    expected: P::BUF_SIZE,
+                    got: buf.len(),
+                });
+            }
+        }
+        self.wait_ready().await?;
+        self.bus.write_cmd(Command::DataStartTransmission1).await?;
+        // The controller wants 1 = white in the black plane.
+        let mut block = [0u8; CHUNK];
+        for src in black.chunks(CHUNK) {
+            let block = &mut block[..src.len()];
+            for (d, s) in block.iter_mut().zip(src) {
+                *d = !s;
+            }
+            self.bus.write_data(block).await?;
+        }
+        self.bus.write_cmd(Command::DataStartTransmission2).await?;
+        self.bus.write_data(red).await?;
+        self.refresh().await
+    }
+
+    /// Blank the panel to white.
+    pub async fn clear(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.wait_ready().await?;
+        self.bus.write_cmd(Command::DataStartTransmission1).await?;
+        self.bus.write_fill(0xFF, P::BUF_SIZE).await?;
+        self.bus.write_cmd(Command::DataStartTransmission2).await?;
+        self.bus.write_fill(0x00, P::BUF_SIZE).await?;
+        self.refresh().await
+    }
+
+    pub async fn sleep(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.bus.write_cmd(Command::PowerOff).await?;
+        self.wait_busy(BusyOp::PowerOff).await?;
+        self.bus.write_cmd(Command::DeepSleep).await?;
+        self.bus.write_data(&[0xA5]).await?;
+        Ok(())
+    }
+
+    async fn refresh(&mut self) -> Result<(), DriverError<SPI, CS, DC, RST, BUSY>> {
+        self.bus.write_cmd(Command::DisplayRefresh).await?;
+        self.bus.delay_ms(100).await;
+        self.wait_ready().await
+    }
+}
@@ -0,0 +1,593 @@
+//! Full-screen 1bpp framebuffer with dirty-region tracking.
+//!
+//! Drawing through [`DrawTarget`] records, per row, the span of bytes that
+//! actually changed. [`Framebuffer::flush`] turns those spans into a few
+//! byte-aligned rectangles and sends them with partial refreshes, or falls
+//! back to a full refresh when most of the screen changed.
+//!
+//! All framebuffers here are generic over the [`PanelSpec`] and default to
+//! the 7.5" 800x480 panel.
+//!
+//! All can be rotated or mirrored with `set_orientation`; dirty regions
+//! are tracked on the panel, so flushed rectangles are already rotated.
+//!
+//! [`GrayFramebuffer`] is the 2bpp counterpart for `display_gray`, and
+//! [`TriColorFramebuffer`] holds the two planes of the black/white/red panel.
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{OriginDimensions, Size},
+    pixelcolor::{BinaryColor, Gray2, GrayColor},
+    prelude::*,
+};
+use heapless::Vec as HVec;
+
+use crate::epd_driver::{EpdDriver, Panel7in5V2, PanelSpec, Rect};
+use crate::orientation::{self, Orientation};
+
+/// Tallest panel a [`Framebuffer`] can track.
+pub const MAX_ROWS: usize = 800;
+
+/// Most partial windows sent by one flush; further regions get merged.
+pub const MAX_REGIONS: usize = 4;
+
+/// Dirty spans closer than this many rows are merged into one region.
+const MERGE_GAP: usize = 8;
+
+/// What a [`Framebuffer::flush`] sent to the panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flush {
+    /// Nothing changed since the last flush.
+    None,
+    /// This many partial windows were refreshed.
+    Partial(usize),
+    /// The whole screen was refreshed.
+    Full,
+}
+
+/// Why [`Framebuffer::blit`] rejected a window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlitError {
+    /// The window does not lie inside the panel.
+    OutOfBounds,
+    /// The source is not `r.w.div_ceil(8) * r.h` bytes.
+    BadLen { expected: usize, got: usize },
+}
+
+/// Changed byte columns `[lo, hi]` of one row; `lo > hi` means clean.
+#[derive(Clone, Copy)]
+struct Span {
+    lo: u8,
+    hi: u8,
+}
+
+impl Span {
+    const CLEAN: Span = Span { lo: u8::MAX, hi: 0 };
+
+    fn is_dirty(&self) -> bool {
+        self.lo <= self.hi
+    }
+}
+
+/// MSB-first framebuffer (`1` = black) that remembers what changed.
+pub struct Framebuffer<'a, P = Panel7in5V2> {
+    buf: &'a mut [u8],
+    dirty: [Span; MAX_ROWS],
+    full_threshold: usize,
+    orientation: Orientation,
+    panel: PhantomData<P>,
+}
+
+impl<'a> Framebuffer<'a> {
+    /// Wrap a `BUF_SIZE` byte buffer for the 800x480 panel. Its current
+    /// contents are assumed to be on screen already.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self::for_panel(buf)
+    }
+}
+
+impl<'a, P: PanelSpec> Framebuffer<'a, P> {
+    /// Wrap a `P::BUF_SIZE` byte buffer. Its current contents are assumed to
+    /// be on screen already.
+    pub fn for_panel(buf: &'a mut [u8]) -> Self {
+        assert_eq!(buf.len(), P::BUF_SIZE, "framebuffer must be BUF_SIZE bytes");
+        assert!(
+            P::HEIGHT <= MAX_ROWS && P::WIDTH <= 8 * 256,
+            "panel too large for dirty tracking"
+        );
+        Self {
+            buf,
+            dirty: [Span::CLEAN; MAX_ROWS],
+            full_threshold: 50,
+            orientation: Orientation::default(),
+            panel: PhantomData,
+        }
+    }
+
+    /// Do a full refresh instead of partial ones once the dirty regions cover
+    /// at least `percent` of the screen (default 50).
+    pub fn set_full_threshold(&mut self, percent: usize) {
+        self.full_threshold = percent;
+    }
+
+    /// Rotate or mirror what is drawn from now on. The buffer itself stays
+    /// in panel order.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.orientation = orientation;
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    pub fn buffer(&self) -> &[u8] {
+        self.buf
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.iter().any(Span::is_dirty)
+    }
+
+    /// Force the next flush to resend everything.
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty[..P::HEIGHT].fill(Span {
+            lo: 0,
+            hi: (P::WIDTH / 8 - 1) as u8,
+        });
+    }
+
+    /// Forget pending changes, e.g. after sending the buffer by other means.
+    pub fn mark_clean(&mut self) {
+        self.dirty = [Span::CLEAN; MAX_ROWS];
+    }
+
+    /// Forget pending changes of rows whose changes lie entirely within the
+    /// panel rectangle `r`, after `r` was sent by other means.
+    pub(crate) fn mark_clean_in(&mut self, r: Rect) {
+        let (lo, hi) = (r.x / 8, (r.x + r.w) / 8);
+        for span in self.dirty.iter_mut().take(P::HEIGHT).skip(r.y).take(r.h) {
+            if span.is_dirty() && span.lo as usize >= lo && (span.hi as usize) < hi {
+                *span = Span::CLEAN;
+            }
+        }
+    }
+
+    /// Copy a packed window into the buffer at `(r.x, r.y)` in drawing
+    /// coordinates, which need not be byte aligned. `bits` holds `r.h` rows
+    /// of `r.w.div_ceil(8)` bytes, MSB-first; the padding bits ending each
+    /// row are ignored.
+    ///
+    /// Pixels next to the window keep their values, so the byte-aligned
+    /// region the next flush sends shows them unchanged. Returns that
+    /// region on the panel, i.e. `r` rotated and widened to the panel's
+    /// `X_ALIGN`.
+    pub fn blit(&mut self, bits: &[u8], r: Rect) -> Result<Rect, BlitError> {
+        let fits = |start: usize, len: usize, max: usize| {
+            start.checked_add(len).is_some_and(|end| end <= max)
+        };
+        let (w, h) = self.orientation.size(P::WIDTH, P::HEIGHT);
+        if !fits(r.x, r.w, w) || !fits(r.y, r.h, h) {
+            return Err(BlitError::OutOfBounds);
+        }
+        let stride = r.w.div_ceil(8);
+        if bits.len() != stride * r.h {
+            return Err(BlitError::BadLen {
+                expected: stride * r.h,
+                got: bits.len(),
+            });
+        }
+        for (dy, row) in bits.chunks_exact(stride.max(1)).take(r.h).enumerate() {
+            for dx in 0..r.w {
+                let on = row[dx / 8] & (0x80 >> (dx % 8)) != 0;
+                self.set_pixel(r.x + dx, r.y + dy, on);
+            }
+        }
+        Ok(self.panel_rect(r))
+    }
+
+    /// Partially refresh `r` (in drawing coordinates), rotated and widened
+    /// to the panel's `X_ALIGN`, whether or not it changed. Changes outside
+    /// it stay pending.
+    pub async fn flush_rect<D>(&mut self, epd: &mut D, r: Rect) -> Result<(), D::Error>
+    where
+        D: EpdDriver<Panel = P>,
+    {
+        let r = self.panel_rect(r);
+        epd.display_region(self.buf, r).await?;
+        self.mark_clean_in(r);
+        Ok(())
+    }
+
+    /// The panel rectangle covering `r` in drawing coordinates, aligned for
+    /// a partial refresh.
+    fn panel_rect(&self, r: Rect) -> Rect {
+        let r = self.orientation.rect_to_panel(r, P::WIDTH, P::HEIGHT);
+        orientation::align_x(r, P::X_ALIGN)
+    }
+
+    #[inline]
+    fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        let (w, h) = self.orientation.size(P::WIDTH, P::HEIGHT);
+        if x >= w || y >= h {
+            return;
+        }
+        let (x, y) = self.orientation.to_panel(x, y, P::WIDTH, P::HEIGHT);
+        self.set_panel_pixel(x, y, on);
+    }
+
+    #[inline]
+    fn set_panel_pixel(&mut self, x: usize, y: usize, on: bool) {
+        let col = x / 8;
+        let byte = &mut self.buf[y * (P::WIDTH / 8) + col];
+        let mask = 0x80 >> (x % 8);
+        let old = *byte;
+        if on {
+            *byte |= mask;
+        } else {
+            *byte &= !mask;
+        }
+        if *byte != old {
+            let span = &mut self.dirty[y];
+            span.lo = span.lo.min(col as u8);
+            span.hi = span.hi.max(col as u8);
+        }
+    }
+
+    /// Merge the dirty rows into at most [`MAX_REGIONS`] rectangles aligned
+    /// to the panel's `X_ALIGN`, top to bottom.
+    pub fn dirty_regions(&self) -> HVec<Rect, MAX_REGIONS> {
+        let mut out: HVec<Rect, MAX_REGIONS> = HVec::new();
+        let align = (P::X_ALIGN / 8).max(1);
+        let mut y = 0;
+        while y < P::HEIGHT {
+            if !self.dirty[y].is_dirty() {
+                y += 1;
+                continue;
+            }
+            let (mut lo, mut hi, y0) = (self.dirty[y].lo, self.dirty[y].hi, y);
+            while y < P::HEIGHT && self.dirty[y].is_dirty() {
+                lo = lo.min(self.dirty[y].lo);
+                hi = hi.max(self.dirty[y].hi);
+                y += 1;
+            }
+            let lo = lo as usize / align * align;
+            let hi = ((hi as usize / align + 1) * align).min(P::WIDTH / 8);
+            let band = Rect {
+                x: lo * 8,
+                y: y0,
+                w: (hi - lo) * 8,
+                h: y - y0,
+            };
+            match out.last_mut() {
+                Some(prev) if band.y - (prev.y + prev.h) <= MERGE_GAP => *prev = union(*prev, band),
+                _ => {
+                    if out.is_full() {
+                        merge_closest(&mut out);
+                    }
+                    let _ = out.push(band);
+                }
+            }
+        }
+        out
+    }
+
+    /// Send pending changes to the panel: partial refreshes of the dirty
+    /// regions, or one full refresh if they cover too much of the screen.
+    pub async fn flush<D>(&mut self, epd: &mut D) -> Result<Flush, D::Error>
+    where
+        D: EpdDriver<Panel = P>,
+    {
+        let regions = self.dirty_regions();
+        if regions.is_empty() {
+            return Ok(Flush::None);
+        }
+
+        let area: usize = regions.iter().map(|r| r.w * r.h).sum();
+        if area * 100 >= P::WIDTH * P::HEIGHT * self.full_threshold {
+            epd.display(self.buf).await?;
+            self.mark_clean();
+            return Ok(Flush::Full);
+        }
+
+        for r in regions.iter() {
+            epd.display_region(self.buf, *r).await?;
+        }
+        self.mark_clean();
+        Ok(Flush::Partial(regions.len()))
+    }
+}
+
+fn union(a: Rect, b: Rect) -> Rect {
+    let x0 = a.x.min(b.x);
+    let y0 = a.y.min(b.y);
+    let x1 = (a.x + a.w).max(b.x + b.w);
+    let y1 = (a.y + a.h).max(b.y + b.h);
+    Rect {
+        x: x0,
+        y: y0,
+        w: x1 - x0,
+        h: y1 - y0,
+    }
+}
+
+/// Merge the two vertically closest neighbours of a sorted region list.
+fn merge_closest(rects: &mut HVec<Rect, MAX_REGIONS>) {
+    let mut best = 0;
+    let mut best_gap = usize::MAX;
+    for i in 0..rects.len() - 1 {
+        let gap = rects[i + 1].y - (rects[i].y + rects[i].h);
+        if gap < best_gap {
+            best = i;
+            best_gap = gap;
+        }
+    }
+    rects[best] = union(rects[best], rects[best + 1]);
+    rects.remove(best + 1);
+}
+
+impl<P: PanelSpec> OriginDimensions for Framebuffer<'_, P> {
+    fn size(&self) -> Size {
+        let (w, h) = self.orientation.size(P::WIDTH, P::HEIGHT);
+        Size::new(w as u32, h as u32)
+    }
+}
+
+impl<P: PanelSpec> DrawTarget for Framebuffer<'_, P> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(coord, color) in pixels {
+            if let (Ok(x), Ok(y)) = (usize::try_from(coord.x), usize::try_from(coord.y)) {
+                self.set_pixel(x, y, color.is_on());
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let v = if color.is_on() { 0xFF } else { 0x00 };
+        for (y, row) in self.buf.chunks_exact_mut(P::WIDTH / 8).enumerate() {
+            for (col, b) in row.iter_mut().enumerate() {
+                if *b != v {
+                    *b = v;
+                    let span = &mut self.dirty[y];
+                    span.lo = span.lo.min(col as u8);
+                    span.hi = span.hi.max(col as u8);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Four-level gray framebuffer: 2bpp, four pixels per byte MSB-first, each
+/// the `Gray2` luma. Pass [`buffer`](Self::buffer) to `Epd::display_gray`.
+pub struct GrayFramebuffer<'a, P = Panel7in5V2> {
+    buf: &'a mut [u8],
+    orientation: Orientation,
+    panel: PhantomData<P>,
+}
+
+impl<'a> GrayFramebuffer<'a> {
+    /// Wrap a `GRAY_BUF_SIZE` byte buffer for the 800x480 panel.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self::for_panel(buf)
+    }
+}
+
+impl<'a, P: PanelSpec> GrayFramebuffer<'a, P> {
+    /// Wrap a `P::GRAY_BUF_SIZE` byte buffer.
+    pub fn for_panel(buf: &'a mut [u8]) -> Self {
+        assert_eq!(
+            buf.len(),
+            P::GRAY_BUF_SIZE,
+            "gray framebuffer must be GRAY_BUF_SIZE bytes"
+        );
+        Self {
+            buf,
+            orientation: Orientation::default(),
+            panel: PhantomData,
+        }
+    }
+
+    /// Rotate or mirror what is drawn from now on.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.orientation = orientation;
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    pub fn buffer(&self) -> &[u8] {
+        self.buf
+    }
+
+    /// Pixel at `(x, y)` in drawing coordinates.
+    pub fn pixel(&self, x: usize, y: usize) -> Gray2 {
+        let (x, y) = self.orientation.to_panel(x, y, P::WIDTH, P::HEIGHT);
+        let idx = y * P::WIDTH + x;
+        Gray2::new((self.buf[idx / 4] >> (6 - 2 * (idx % 4))) & 0b11)
+    }
+
+    #[inline]
+    fn set_pixel(&mut self, x: usize, y: usize, c: Gray2) {
+        let (w, h) = self.orientation.size(P::WIDTH, P::HEIGHT);
+        if x >= w || y >= h {
+            return;
+        }
+        let (x, y) = self.orientation.to_panel(x, y, P::WIDTH, P::HEIGHT);
+        let idx = y * P::WIDTH + x;
+        let shift = 6 - 2 * (idx % 4);
+        let byte = &mut self.buf[idx / 4];
+        *byte = (*byte & !(0b11 << shift)) | (c.luma() << shift);
+    }
+}
+
+impl<P: PanelSpec> OriginDimensions for GrayFramebuffer<'_, P> {
+    fn size(&self) -> Size {
+        let (w, h) = self.orientation.size(P::WIDTH, P::HEIGHT);
+        Size::new(w as u32, h as u32)
+    }
+}
+
+impl<P: PanelSpec> DrawTarget for GrayFramebuffer<'_, P> {
+    type Color = Gray2;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(coord, color) in pixels {
+            if let (Ok(x), Ok(y)) = (usize::try_from(coord.x), usize::try_from(coord.y)) {
+                self.set_pixel(x, y, color);
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let l = color.luma();
+        self.buf.fill(l << 6 | l << 4 | l << 2 | l);
+        Ok(())
+    }
+}
+
+/// Pixel color of the black/white/red panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriColor {
+    White,
+    Black,
+    Red,
+}
+
+impl PixelColor for TriColor {
+    type Raw = ();
+}
+
+impl From<BinaryColor> for TriColor {
+    fn from(c: BinaryColor) -> Self {
+        if c.is_on() {
+            TriColor::Black
+        } else {
+            TriColor::White
+        }
+    }
+}
+
+/// Black/white/red framebuffer over two 1bpp planes (`1` = ink), for the
+/// panel `P`. Pass [`black`](Self::black) and [`red`](Self::red) to
+/// `EpdBwr::display`.
+pub struct TriColorFramebuffer<'a, P = Panel7in5V2> {
+    black: &'a mut [u8],
+    red: &'a mut [u8],
+    orientation: Orientation,
+    panel: PhantomData<P>,
+}
+
+impl<'a> TriColorFramebuffer<'a> {
+    /// Wrap two `BUF_SIZE` byte planes for the 800x480 panel.
+    pub fn new(black: &'a mut [u8], red: &'a mut [u8]) -> Self {
+        Self::for_panel(black, red)
+    }
+}
+
+impl<'a, P: PanelSpec> TriColorFramebuffer<'a, P> {
+    /// Wrap two `P::BUF_SIZE` byte planes.
+    pub fn for_panel(black: &'a mut [u8], red: &'a mut [u8]) -> Self {
+        assert_eq!(
+            black.len(),
+            P::BUF_SIZE,
+            "black plane must be BUF_SIZE bytes"
+        );
+        assert_eq!(red.len(), P::BUF_SIZE, "red plane must be BUF_SIZE bytes");
+        Self {
+            black,
+            red,
+            orientation: Orientation::default(),
+            panel: PhantomData,
+        }
+    }
+
+    /// Rotate or mirror what is drawn from now on.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.orientation = orientation;
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    pub fn black(&self) -> &[u8] {
+        self.black
+    }
+
+    pub fn red(&self) -> &[u8] {
+        self.red
+    }
+
+    #[inline]
+    fn set_pixel(&mut self, x: usize, y: usize, c: TriColor) {
+        let (w, h) = self.orientation.size(P::WIDTH, P::HEIGHT);
+        if x >= w || y >= h {
+            return;
+        }
+        let (x, y) = self.orientation.to_panel(x, y, P::WIDTH, P::HEIGHT);
+        let i = y * (P::WIDTH / 8) + x / 8;
+        let mask = 0x80 >> (x % 8);
+        let (b, r) = match c {
+            TriColor::White => (false, false),
+            TriColor::Black => (true, false),
+            TriColor::Red => (false, true),
+        };
+        if b {
+            self.black[i] |= mask;
+        } else {
+            self.black[i] &= !mask;
+        }
+        if r {
+            self.red[i] |= mask;
+        } else {
+            self.red[i] &= !mask;
+        }
+    }
+}
+
+impl<P: PanelSpec> OriginDimensions for TriColorFramebuffer<'_, P> {
+    fn size(&self) -> Size {
+        let (w, h) = self.orientation.size(P::WIDTH, P::HEIGHT);
+        Size::new(w as u32, h as u32)
+    }
+}
+
+impl<P: PanelSpec> DrawTarget for TriColorFramebuffer<'_, P> {
+    type Color = TriColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(coord, color) in pixels {
+            if let (Ok(x), Ok(y)) = (usize::try_from(coord.x), usize::try_from(coord.y)) {
+                self.set_pixel(x, y, color);
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.black
+            .fill(if color == TriColor::Black { 0xFF } else { 0x00 });
+        self.red
+            .fill(if color == TriColor::Red { 0xFF } else { 0x00 });
+        Ok(())
+    }
+}
@@ -1,4 +1,10 @@
 #![no_std]
+#[cfg(feature = "std")]
+extern crate std;
+
 pub mod console;
+pub mod display;
 pub mod epd_driver;
+pub mod framebuffer;
+pub mod orientation;
 pub mod ui;
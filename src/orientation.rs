@@ -0,0 +1,112 @@
+//! Rotation and mirroring between drawing coordinates and the panel.
+//!
+//! The draw targets keep their buffers in the panel's native row-major
+//! layout; an [`Orientation`] only changes how the coordinates you draw
+//! with map onto it. A 800x480 panel with [`Rotation::Deg90`] is drawn on as
+//! a 480x800 portrait screen.
+
+use crate::epd_driver::Rect;
+
+/// Clockwise rotation of the picture on the panel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+/// How drawing coordinates map onto the panel. Mirroring flips the
+/// drawing coordinates before they are rotated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Orientation {
+    pub rotation: Rotation,
+    /// Flip left and right.
+    pub mirror_x: bool,
+    /// Flip top and bottom.
+    pub mirror_y: bool,
+}
+
+impl Orientation {
+    pub const fn new(rotation: Rotation) -> Self {
+        Self {
+            rotation,
+            mirror_x: false,
+            mirror_y: false,
+        }
+    }
+
+    pub const fn mirrored_x(mut self) -> Self {
+        self.mirror_x = !self.mirror_x;
+        self
+    }
+
+    pub const fn mirrored_y(mut self) -> Self {
+        self.mirror_y = !self.mirror_y;
+        self
+    }
+
+    /// `true` for 90° and 270°, where width and height swap.
+    pub const fn swaps_axes(&self) -> bool {
+        matches!(self.rotation, Rotation::Deg90 | Rotation::Deg270)
+    }
+
+    /// Drawing size of a `panel_w` x `panel_h` panel.
+    pub const fn size(&self, panel_w: usize, panel_h: usize) -> (usize, usize) {
+        if self.swaps_axes() {
+            (panel_h, panel_w)
+        } else {
+            (panel_w, panel_h)
+        }
+    }
+
+    /// Panel position of the drawing position `(x, y)`, which must lie
+    /// within [`size`](Self::size).
+    pub const fn to_panel(
+        &self,
+        x: usize,
+        y: usize,
+        panel_w: usize,
+        panel_h: usize,
+    ) -> (usize, usize) {
+        let (w, h) = self.size(panel_w, panel_h);
+        let x = if self.mirror_x { w - 1 - x } else { x };
+        let y = if self.mirror_y { h - 1 - y } else { y };
+        match self.rotation {
+            Rotation::Deg0 => (x, y),
+            Rotation::Deg90 => (panel_w - 1 - y, x),
+            Rotation::Deg180 => (panel_w - 1 - x, panel_h - 1 - y),
+            Rotation::Deg270 => (y, panel_h - 1 - x),
+        }
+    }
+
+    /// The panel rectangle covering the drawing rectangle `r`, e.g. for a
+    /// partial refresh. Align it to the panel's `X_ALIGN` before sending.
+    pub fn rect_to_panel(&self, r: Rect, panel_w: usize, panel_h: usize) -> Rect {
+        if r.w == 0 || r.h == 0 {
+            let (x, y) = self.to_panel(r.x, r.y, panel_w, panel_h);
+            return Rect { x, y, w: 0, h: 0 };
+        }
+        let (ax, ay) = self.to_panel(r.x, r.y, panel_w, panel_h);
+        let (bx, by) = self.to_panel(r.x + r.w - 1, r.y + r.h - 1, panel_w, panel_h);
+        Rect {
+            x: ax.min(bx),
+            y: ay.min(by),
+            w: ax.abs_diff(bx) + 1,
+            h: ay.abs_diff(by) + 1,
+        }
+    }
+}
+
+/// Widen `r` horizontally so both edges sit on multiples of `align` pixels.
+pub fn align_x(r: Rect, align: usize) -> Rect {
+    let x0 = r.x / align * align;
+    let x1 = (r.x + r.w).div_ceil(align) * align;
+    Rect {
+        x: x0,
+        y: r.y,
+        w: x1 - x0,
+        h: r.h,
+    }
+}
@@ -1,5 +1,7 @@
 extern crate alloc;
 
+pub mod dither;
+
 use alloc::boxed::Box;
 use alloc::vec;
 use alloc::vec::Vec;
@@ -9,9 +11,22 @@ use crate::epd_driver::Rect;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PackError {
-    BadInputLen { expected: usize, got: usize },
-    OutBufTooSmall { needed: usize, got: usize },
+    BadInputLen {
+        expected: usize,
+        got: usize,
+    },
+    OutBufTooSmall {
+        needed: usize,
+        got: usize,
+    },
     Overflow,
+    /// The window does not lie inside the frame given to [`pack_bitmap_onto`].
+    OutOfBounds,
+    /// The frame given to [`pack_bitmap_onto`] is shorter than its size.
+    FrameTooSmall {
+        needed: usize,
+        got: usize,
+    },
 }
 
 #[inline]
@@ -26,6 +41,50 @@ pub fn pack_bitmap<T: BitStore>(
     y: usize,
     w: usize,
     h: usize,
+) -> Result<(Rect, Box<[u8]>), PackError> {
+    pack(bits, x, y, w, h, None)
+}
+
+/// Like [`pack_bitmap`], but the padding bits around an unaligned window are
+/// taken from `frame`, the `frame_w` x `frame_h` picture on screen (rows of
+/// `frame_w.div_ceil(8)` bytes), so a partial refresh of the returned rect
+/// leaves the neighbouring pixels as they are.
+#[allow(clippy::too_many_arguments)]
+pub fn pack_bitmap_onto<T: BitStore>(
+    frame: &[u8],
+    frame_w: usize,
+    frame_h: usize,
+    bits: &BitSlice<T, Msb0>, // len == w*h
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+) -> Result<(Rect, Box<[u8]>), PackError> {
+    let stride = frame_w.div_ceil(8);
+    let needed = stride.checked_mul(frame_h).ok_or(PackError::Overflow)?;
+    if frame.len() < needed {
+        return Err(PackError::FrameTooSmall {
+            needed,
+            got: frame.len(),
+        });
+    }
+    let fits =
+        |start: usize, len: usize, max: usize| start.checked_add(len).is_some_and(|end| end <= max);
+    if !fits(x, w, frame_w) || !fits(y, h, frame_h) {
+        return Err(PackError::OutOfBounds);
+    }
+    pack(bits, x, y, w, h, Some((frame, stride)))
+}
+
+/// `background` is the frame to pad from and its bytes per row; `None`
+/// pads with white.
+fn pack<T: BitStore>(
+    bits: &BitSlice<T, Msb0>,
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+    background: Option<(&[u8], usize)>,
 ) -> Result<(Rect, Box<[u8]>), PackError> {
     if w == 0 || h == 0 {
         let rect = Rect {
@@ -57,20 +116,23 @@ pub fn pack_bitmap<T: BitStore>(
         // Source slice for this row: exactly `w` bits
         let row_bits = &bits[row * w..row * w + w];
         let line = &mut buf[row * line_bytes..(row + 1) * line_bytes];
-        // Clear line (already zeroed by vec![0; ...], but keep explicit for clarity)
-        // for b in line.iter_mut() { *b = 0; }
+        if let Some((frame, frame_row)) = background {
+            let start = (y + row) * frame_row + x_aligned / 8;
+            line.copy_from_slice(&frame[start..start + line_bytes]);
+        }
 
         // Place each source bit at destination position `shift + k`
         // MSB-first: within a byte, bit 7 is the leftmost pixel.
         for (k, bit) in row_bits.iter().by_vals().enumerate() {
-            if !bit {
-                continue;
-            }
             let pos = shift + k; // bit index in the destination scanline
             let byte_ix = pos / 8;
             let bit_in_byte = 7 - (pos % 8); // MSB-first
             // Safety: byte_ix < line_bytes by construction of w_aligned_bits
-            line[byte_ix] |= 1u8 << bit_in_byte;
+            if bit {
+                line[byte_ix] |= 1u8 << bit_in_byte;
+            } else {
+                line[byte_ix] &= !(1u8 << bit_in_byte);
+            }
         }
     }
 
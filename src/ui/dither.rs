@@ -0,0 +1,272 @@
+//! Dithering of grayscale and color images onto the 1bpp panel.
+//!
+//! The output has the layout [`pack_bitmap`](super::pack_bitmap) produces
+//! for a byte-aligned window: row-major, `w.div_ceil(8)` bytes per row,
+//! MSB-first, `1` = black, padding bits white. A full-width image can be
+//! passed to `display` directly, a narrower one to `display_partial`.
+//!
+//! ```ignore
+//! let raw = ImageRaw::<Gray8>::new(PHOTO, 320);
+//! let bits = dither_image(&raw, Dither::FloydSteinberg)?;
+//! epd.display_partial(&bits, Rect::new::<Panel>(0, 0, 320, 240)?).await?;
+//! ```
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{OriginDimensions, Size},
+    image::ImageDrawable,
+    pixelcolor::{Gray8, GrayColor},
+    prelude::*,
+    primitives::Rectangle,
+};
+
+use super::PackError;
+
+/// How gray levels are turned into black and white pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    /// Black below this luma, white from it up. No dithering.
+    Threshold(u8),
+    /// Ordered dithering with an 8x8 Bayer matrix. Regular pattern, but
+    /// every pixel depends only on its own value, so it suits charts and
+    /// partial updates.
+    Bayer,
+    /// Error diffusion to four neighbours, keeping all of the error. The
+    /// best tonal range for photos.
+    FloydSteinberg,
+    /// Error diffusion that drops a quarter of the error, giving more
+    /// contrast and cleaner highlights and shadows.
+    Atkinson,
+}
+
+/// Dither the `w` x `h` image `pixels` (row-major) into a new packed buffer.
+pub fn dither<C>(pixels: &[C], w: usize, h: usize, algo: Dither) -> Result<Box<[u8]>, PackError>
+where
+    C: Copy + Into<Gray8>,
+{
+    let mut out = vec![0u8; packed_len(w, h)?];
+    dither_into(pixels, w, h, algo, &mut out)?;
+    Ok(out.into_boxed_slice())
+}
+
+/// Dither the `w` x `h` image `pixels` (row-major) into `out`, which needs
+/// at least `w.div_ceil(8) * h` bytes.
+pub fn dither_into<C>(
+    pixels: &[C],
+    w: usize,
+    h: usize,
+    algo: Dither,
+    out: &mut [u8],
+) -> Result<(), PackError>
+where
+    C: Copy + Into<Gray8>,
+{
+    let expected = w.checked_mul(h).ok_or(PackError::Overflow)?;
+    if pixels.len() != expected {
+        return Err(PackError::BadInputLen {
+            expected,
+            got: pixels.len(),
+        });
+    }
+    let needed = packed_len(w, h)?;
+    if out.len() < needed {
+        return Err(PackError::OutBufTooSmall {
+            needed,
+            got: out.len(),
+        });
+    }
+    dither_rows(w, h, algo, &mut out[..needed], |y, row| {
+        for (l, &p) in row.iter_mut().zip(&pixels[y * w..(y + 1) * w]) {
+            *l = p.into().luma();
+        }
+    });
+    Ok(())
+}
+
+/// Dither an embedded-graphics image, e.g. an `ImageRaw<Gray8>` or a
+/// decoded BMP or TGA, at its own size.
+///
+/// The image is read one row at a time, so apart from the result only a
+/// row of luma and the error rows of the diffusion kernels are held.
+pub fn dither_image<I>(image: &I, algo: Dither) -> Result<Box<[u8]>, PackError>
+where
+    I: ImageDrawable,
+    I::Color: Into<Gray8>,
+{
+    let size = image.size();
+    let (w, h) = (size.width as usize, size.height as usize);
+    let mut out = vec![0u8; packed_len(w, h)?];
+    dither_rows(w, h, algo, &mut out, |y, row| {
+        let area = Rectangle::new(Point::new(0, y as i32), Size::new(size.width, 1));
+        let mut line = Line::<I::Color> {
+            luma: row,
+            color: PhantomData,
+        };
+        line.luma.fill(255);
+        image.draw_sub_image(&mut line, &area).ok();
+    });
+    Ok(out.into_boxed_slice())
+}
+
+/// Dither `h` rows of `w` pixels into `out`, which must be exactly
+/// `packed_len(w, h)` bytes. `fill(y, luma)` supplies the luma of row `y`.
+fn dither_rows(
+    w: usize,
+    h: usize,
+    algo: Dither,
+    out: &mut [u8],
+    mut fill: impl FnMut(usize, &mut [u8]),
+) {
+    out.fill(0);
+    if out.is_empty() {
+        return;
+    }
+    let stride = w.div_ceil(8);
+    debug_assert_eq!(out.len(), stride * h);
+    let mut luma = vec![0u8; w];
+    let mut diffuser = match algo {
+        Dither::FloydSteinberg => Some(Diffuser::new(w, &FLOYD_STEINBERG, 16)),
+        Dither::Atkinson => Some(Diffuser::new(w, &ATKINSON, 8)),
+        Dither::Threshold(_) | Dither::Bayer => None,
+    };
+
+    for (y, row) in out.chunks_exact_mut(stride).enumerate() {
+        fill(y, &mut luma);
+        match (&mut diffuser, algo) {
+            (Some(diffuser), _) => diffuser.row(&luma, row),
+            (None, Dither::Threshold(t)) => {
+                for (x, &l) in luma.iter().enumerate() {
+                    set_black(row, x, l < t);
+                }
+            }
+            (None, _) => {
+                for (x, &l) in luma.iter().enumerate() {
+                    set_black(row, x, l < bayer_threshold(x, y));
+                }
+            }
+        }
+    }
+}
+
+fn packed_len(w: usize, h: usize) -> Result<usize, PackError> {
+    w.div_ceil(8).checked_mul(h).ok_or(PackError::Overflow)
+}
+
+#[inline]
+fn set_black(row: &mut [u8], x: usize, black: bool) {
+    if black {
+        row[x / 8] |= 0x80 >> (x % 8);
+    }
+}
+
+/// Luma threshold of `(x, y)` for ordered dithering: the Bayer index
+/// 0..64 spread evenly over 1..=253.
+fn bayer_threshold(x: usize, y: usize) -> u8 {
+    // Bit-reversed interleaving of `x ^ y` and `y`.
+    let xy = x ^ y;
+    let mut index = 0;
+    for bit in 0..3 {
+        index = (index << 2) | ((xy >> bit) & 1) << 1 | ((y >> bit) & 1);
+    }
+    ((2 * index + 1) * 255 / 128) as u8
+}
+
+/// Share of the error, as `(dx, dy, weight)`, that goes to a neighbour.
+type Kernel = [(isize, usize, i16)];
+
+const FLOYD_STEINBERG: [(isize, usize, i16); 4] = [(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)];
+
+const ATKINSON: [(isize, usize, i16); 6] = [
+    (1, 0, 1),
+    (2, 0, 1),
+    (-1, 1, 1),
+    (0, 1, 1),
+    (1, 1, 1),
+    (0, 2, 1),
+];
+
+/// Error diffusion over `kernel`, whose weights are divided by `div`. Only
+/// the rows the kernel reaches are kept (two for Floyd-Steinberg, three for
+/// Atkinson), each padded by two pixels on both sides so the kernel never
+/// needs bounds checks.
+struct Diffuser {
+    kernel: &'static Kernel,
+    div: i16,
+    err: Vec<Vec<i16>>,
+}
+
+impl Diffuser {
+    const PAD: usize = 2;
+
+    fn new(w: usize, kernel: &'static Kernel, div: i16) -> Self {
+        let rows = kernel.iter().map(|k| k.1).max().unwrap_or(0) + 1;
+        Self {
+            kernel,
+            div,
+            err: vec![vec![0; w + 2 * Self::PAD]; rows],
+        }
+    }
+
+    /// Dither one row of `luma` into `out`, carrying the error down.
+    fn row(&mut self, luma: &[u8], out: &mut [u8]) {
+        for (x, &l) in luma.iter().enumerate() {
+            let v = l as i16 + self.err[0][x + Self::PAD];
+            let black = v < 128;
+            set_black(out, x, black);
+            let e = if black { v } else { v - 255 };
+            for &(dx, dy, weight) in self.kernel {
+                let i = (x + Self::PAD).wrapping_add_signed(dx);
+                self.err[dy][i] += share(e, weight, self.div);
+            }
+        }
+        self.err.rotate_left(1);
+        if let Some(last) = self.err.last_mut() {
+            last.fill(0);
+        }
+    }
+}
+
+/// `e * weight / div`, rounded to the nearest integer (halves away from
+/// zero) rather than truncated, so small errors are not lost.
+fn share(e: i16, weight: i16, div: i16) -> i16 {
+    let n = e * weight;
+    (n + n.signum() * (div / 2)) / div
+}
+
+/// Receives one row of an image as luma.
+struct Line<'a, C> {
+    luma: &'a mut [u8],
+    color: PhantomData<C>,
+}
+
+impl<C> OriginDimensions for Line<'_, C> {
+    fn size(&self) -> Size {
+        Size::new(self.luma.len() as u32, 1)
+    }
+}
+
+impl<C: PixelColor + Into<Gray8>> DrawTarget for Line<'_, C> {
+    type Color = C;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(p, color) in pixels {
+            if p.y == 0
+                && let Some(l) = usize::try_from(p.x).ok().and_then(|x| self.luma.get_mut(x))
+            {
+                *l = color.into().luma();
+            }
+        }
+        Ok(())
+    }
+}
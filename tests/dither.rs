@@ -0,0 +1,142 @@
+//! Dithering into the packed 1bpp format.
+
+use embedded_graphics::image::ImageRaw;
+use embedded_graphics::pixelcolor::{Gray8, Rgb888};
+use pico_epd_driver::ui::PackError;
+use pico_epd_driver::ui::dither::{Dither, dither, dither_image, dither_into};
+
+const ALL: [Dither; 4] = [
+    Dither::Threshold(128),
+    Dither::Bayer,
+    Dither::FloydSteinberg,
+    Dither::Atkinson,
+];
+
+fn flat(luma: u8, w: usize, h: usize) -> Vec<Gray8> {
+    vec![Gray8::new(luma); w * h]
+}
+
+fn black_pixels(bits: &[u8], w: usize, h: usize) -> usize {
+    let stride = w.div_ceil(8);
+    (0..h)
+        .flat_map(|y| (0..w).map(move |x| (x, y)))
+        .filter(|&(x, y)| bits[y * stride + x / 8] & (0x80 >> (x % 8)) != 0)
+        .count()
+}
+
+#[test]
+fn solid_images_stay_solid() {
+    for algo in ALL {
+        let black = dither(&flat(0, 12, 5), 12, 5, algo).unwrap();
+        // 12 pixels per row: the last four bits of each row are padding.
+        assert!(black.chunks(2).all(|r| r == [0xFF, 0xF0]), "{algo:?}");
+        let white = dither(&flat(255, 12, 5), 12, 5, algo).unwrap();
+        assert!(white.iter().all(|&b| b == 0), "{algo:?}");
+    }
+}
+
+#[test]
+fn threshold_and_bayer() {
+    let ramp: Vec<Gray8> = (0..8).map(|i| Gray8::new(i * 32 + 16)).collect();
+    assert_eq!(
+        *dither(&ramp, 8, 1, Dither::Threshold(100)).unwrap(),
+        [0b1110_0000]
+    );
+
+    // Each of the 64 Bayer thresholds is hit once per 8x8 tile, so mid
+    // gray comes out as exactly half black, in the same tile every time.
+    let bits = dither(&flat(128, 16, 16), 16, 16, Dither::Bayer).unwrap();
+    assert_eq!(black_pixels(&bits, 16, 16), 128);
+    let quarter = dither(&flat(64, 8, 8), 8, 8, Dither::Bayer).unwrap();
+    assert_eq!(black_pixels(&quarter, 8, 8), 48);
+    assert_eq!(bits[0], bits[16]);
+}
+
+#[test]
+fn error_diffusion_keeps_the_tone() {
+    let (w, h) = (64, 64);
+    for (luma, lo, hi) in [(64, 2900, 3250), (128, 1950, 2150), (192, 900, 1150)] {
+        let fs = dither(&flat(luma, w, h), w, h, Dither::FloydSteinberg).unwrap();
+        let n = black_pixels(&fs, w, h);
+        assert!((lo..=hi).contains(&n), "Floyd-Steinberg at {luma}: {n}");
+    }
+    // Atkinson drops part of the error, so light grays lose their dots
+    // first, but mid gray is still mixed.
+    let at = dither(&flat(128, w, h), w, h, Dither::Atkinson).unwrap();
+    let n = black_pixels(&at, w, h);
+    assert!((1500..=2600).contains(&n), "Atkinson at 128: {n}");
+}
+
+#[test]
+fn mid_gray_ramp_is_half_black() {
+    // Columns 0..=255 average to mid gray, so half the pixels end up
+    // black, and the left half (dark) holds about three quarters of them.
+    let (w, h) = (256, 64);
+    let data: Vec<u8> = (0..w * h).map(|i| (i % w) as u8).collect();
+    let ramp: Vec<Gray8> = data.iter().map(|&l| Gray8::new(l)).collect();
+    for algo in [Dither::FloydSteinberg, Dither::Bayer] {
+        let bits = dither(&ramp, w, h, algo).unwrap();
+        let n = black_pixels(&bits, w, h);
+        assert!((8110..=8270).contains(&n), "{algo:?}: {n}");
+        let left: usize = bits
+            .chunks(w / 8)
+            .map(|row| {
+                row[..16]
+                    .iter()
+                    .map(|b| b.count_ones() as usize)
+                    .sum::<usize>()
+            })
+            .sum();
+        assert!((6070..=6240).contains(&left), "{algo:?}: {left}");
+
+        // Streaming the image row by row gives the same bits.
+        let raw = ImageRaw::<Gray8>::new(&data, w as u32);
+        assert_eq!(dither_image(&raw, algo).unwrap(), bits);
+    }
+}
+
+#[test]
+fn small_errors_are_rounded_not_dropped() {
+    // Each pixel of luma 250 leaves an error of 5; truncating the 7/16,
+    // 3/16, 5/16 and 1/16 shares would pass on only 3 of it and give about
+    // 30 dots instead of the ~60 the image gets (80 less what the first
+    // rows need to build up and the edges lose).
+    let (w, h) = (64, 64);
+    let bits = dither(&flat(250, w, h), w, h, Dither::FloydSteinberg).unwrap();
+    let n = black_pixels(&bits, w, h);
+    assert!((50..=90).contains(&n), "{n}");
+}
+
+#[test]
+fn rgb_and_images() {
+    // Pure blue is dark, yellow light.
+    let rgb = [Rgb888::new(0, 0, 255), Rgb888::new(255, 255, 0)];
+    assert_eq!(*dither(&rgb, 2, 1, Dither::Threshold(128)).unwrap(), [0x80]);
+
+    let data: Vec<u8> = (0..32).map(|i| if i % 4 < 2 { 0 } else { 255 }).collect();
+    let raw = ImageRaw::<Gray8>::new(&data, 8);
+    let bits = dither_image(&raw, Dither::FloydSteinberg).unwrap();
+    assert_eq!(*bits, [0b1100_1100; 4]);
+}
+
+#[test]
+fn bad_sizes() {
+    assert_eq!(
+        dither(&flat(0, 4, 4), 5, 4, Dither::Bayer).unwrap_err(),
+        PackError::BadInputLen {
+            expected: 20,
+            got: 16
+        }
+    );
+    let mut out = [0u8; 3];
+    assert_eq!(
+        dither_into(&flat(0, 9, 2), 9, 2, Dither::Bayer, &mut out).unwrap_err(),
+        PackError::OutBufTooSmall { needed: 4, got: 3 }
+    );
+    assert_eq!(
+        dither(&flat(0, 0, 0), 0, 0, Dither::Atkinson)
+            .unwrap()
+            .len(),
+        0
+    );
+}
@@ -0,0 +1,412 @@
+//! Dirty-region tracking and flush decisions, checked against the simulator.
+
+use embassy_futures::block_on;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+use pico_epd_driver::epd_driver::mock::{MockBusy, MockHw, MockPin, MockSpi};
+use pico_epd_driver::epd_driver::sim::PanelSim;
+use pico_epd_driver::epd_driver::{BUF_SIZE, Command, Epd800x480, Rect};
+use pico_epd_driver::framebuffer::{Flush, Framebuffer};
+
+type Epd = Epd800x480<MockSpi, MockPin, MockPin, MockPin, MockBusy, MockPin>;
+
+fn setup() -> (MockHw, Epd, PanelSim) {
+    let hw = MockHw::new();
+    let mut epd = Epd800x480::new(hw.bus(), hw.led());
+    block_on(epd.init()).unwrap();
+    block_on(epd.clear()).unwrap();
+    let mut sim = PanelSim::new();
+    sim.feed(&hw.take_trace());
+    (hw, epd, sim)
+}
+
+fn fill(fb: &mut Framebuffer, x: i32, y: i32, w: u32, h: u32) {
+    Rectangle::new(Point::new(x, y), Size::new(w, h))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(fb)
+        .unwrap();
+}
+
+#[test]
+fn regions_are_byte_aligned_and_merged() {
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut fb = Framebuffer::new(&mut buf);
+    assert!(!fb.is_dirty());
+
+    fill(&mut fb, 13, 10, 5, 3);
+    fill(&mut fb, 40, 15, 2, 2); // close below: merged with the first
+    fill(&mut fb, 100, 200, 10, 10);
+
+    let regions = fb.dirty_regions();
+    assert_eq!(regions.len(), 2);
+    let (a, b) = (regions[0], regions[1]);
+    assert_eq!((a.x, a.y, a.w, a.h), (8, 10, 40, 7));
+    assert_eq!((b.x, b.y, b.w, b.h), (96, 200, 16, 10));
+
+    // Redrawing identical pixels does not dirty anything.
+    fb.mark_clean();
+    fill(&mut fb, 13, 10, 5, 3);
+    assert!(!fb.is_dirty());
+}
+
+#[test]
+fn regions_are_capped() {
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut fb = Framebuffer::new(&mut buf);
+    for i in 0..10 {
+        fill(&mut fb, 0, i * 40, 8, 1);
+    }
+    let regions = fb.dirty_regions();
+    assert_eq!(regions.len(), 4);
+    assert_eq!(regions[0].y, 0);
+    let last = regions[3];
+    assert_eq!(last.y + last.h, 361);
+}
+
+#[test]
+fn blit_merges_unaligned_windows() {
+    use pico_epd_driver::framebuffer::BlitError;
+
+    let (hw, mut epd, mut sim) = setup();
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut fb = Framebuffer::new(&mut buf);
+    fill(&mut fb, 0, 0, 32, 4);
+    block_on(fb.flush(&mut epd)).unwrap();
+
+    // 3 rows of 10 pixels, alternating white and black, at x = 5.
+    let window = [0b0101_0101, 0b0100_0000].repeat(3);
+    let r = Rect {
+        x: 5,
+        y: 1,
+        w: 10,
+        h: 3,
+    };
+    assert_eq!(
+        fb.blit(&window, r),
+        Ok(Rect {
+            x: 0,
+            y: 1,
+            w: 16,
+            h: 3
+        })
+    );
+    assert_eq!(fb.buffer()[100..102], [0b1111_1010, 0b1010_1011]);
+
+    block_on(fb.flush_rect(&mut epd, r)).unwrap();
+    assert!(!fb.is_dirty());
+    sim.feed(&hw.take_trace());
+    assert_eq!(sim.screen().data, fb.buffer());
+
+    let off = Rect {
+        x: 795,
+        y: 0,
+        w: 10,
+        h: 3,
+    };
+    assert_eq!(fb.blit(&window, off), Err(BlitError::OutOfBounds));
+    assert_eq!(
+        fb.blit(&window[..4], r),
+        Err(BlitError::BadLen {
+            expected: 6,
+            got: 4
+        })
+    );
+}
+
+#[test]
+fn blit_follows_the_orientation() {
+    use pico_epd_driver::framebuffer::BlitError;
+    use pico_epd_driver::orientation::{Orientation, Rotation};
+
+    let (hw, mut epd, mut sim) = setup();
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut fb = Framebuffer::new(&mut buf);
+    fb.set_orientation(Orientation::new(Rotation::Deg90));
+
+    // One black row of 8 pixels at the top left of the portrait screen
+    // becomes the rightmost panel column.
+    let r = Rect {
+        x: 0,
+        y: 0,
+        w: 8,
+        h: 1,
+    };
+    assert_eq!(
+        fb.blit(&[0xFF], r),
+        Ok(Rect {
+            x: 792,
+            y: 0,
+            w: 8,
+            h: 8
+        })
+    );
+    for row in 0..8 {
+        assert_eq!(fb.buffer()[row * 100 + 99], 0b0000_0001);
+    }
+
+    block_on(fb.flush_rect(&mut epd, r)).unwrap();
+    assert!(!fb.is_dirty());
+    sim.feed(&hw.take_trace());
+    assert_eq!(sim.screen().data, fb.buffer());
+
+    let off = Rect {
+        x: 475,
+        y: 0,
+        w: 8,
+        h: 1,
+    };
+    assert_eq!(fb.blit(&[0xFF], off), Err(BlitError::OutOfBounds));
+}
+
+#[test]
+fn flush_partial_then_full() {
+    let (hw, mut epd, mut sim) = setup();
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut fb = Framebuffer::new(&mut buf);
+
+    assert_eq!(block_on(fb.flush(&mut epd)).unwrap(), Flush::None);
+
+    fill(&mut fb, 50, 60, 30, 20);
+    assert_eq!(block_on(fb.flush(&mut epd)).unwrap(), Flush::Partial(1));
+    let ops = hw.take_trace();
+    assert!(
+        ops.iter()
+            .any(|o| matches!(o.as_cmd(), Some((Command::PartialIn, _))))
+    );
+    sim.feed(&ops);
+    assert_eq!(sim.screen().data, fb.buffer());
+    assert!(!fb.is_dirty());
+
+    fill(&mut fb, 0, 0, 800, 300);
+    assert_eq!(block_on(fb.flush(&mut epd)).unwrap(), Flush::Full);
+    sim.feed(&hw.take_trace());
+    assert_eq!(sim.screen().data, fb.buffer());
+}
+
+#[test]
+fn gray_frame_is_split_over_both_planes() {
+    use embedded_graphics::pixelcolor::Gray2;
+    use pico_epd_driver::epd_driver::{DisplayMode, GRAY_BUF_SIZE};
+    use pico_epd_driver::framebuffer::GrayFramebuffer;
+
+    let (hw, mut epd, mut sim) = setup();
+    block_on(epd.set_mode(DisplayMode::Fast)).unwrap();
+    hw.take_trace();
+    let mut buf = vec![0u8; GRAY_BUF_SIZE];
+    let mut fb = GrayFramebuffer::new(&mut buf);
+    fb.clear(Gray2::WHITE).unwrap();
+    for (x, luma) in [(0, 3), (1, 2), (2, 1), (3, 0)] {
+        Pixel(Point::new(x, 0), Gray2::new(luma))
+            .draw(&mut fb)
+            .unwrap();
+    }
+    assert_eq!(fb.pixel(1, 0), Gray2::new(2));
+
+    block_on(epd.display_gray(fb.buffer())).unwrap();
+    let ops = hw.take_trace();
+    sim.feed(&ops);
+
+    // darkness 0..3 -> (DTM1, DTM2) = (0,0), (0,1), (1,0), (1,1)
+    assert_eq!(sim.old_plane()[0], 0b0011_0000);
+    assert_eq!(sim.new_plane()[0], 0b0101_0000);
+    assert!(sim.old_plane()[1..].iter().all(|&b| b == 0));
+
+    // Gray LUTs for the refresh, then back to the fast ones.
+    let gray = DisplayMode::Gray4.lut_set();
+    let fast = DisplayMode::Fast.lut_set();
+    let lut_ww: Vec<&[u8]> = ops
+        .iter()
+        .filter_map(|o| o.as_cmd())
+        .filter(|(c, _)| *c == Command::LutWw)
+        .map(|(_, d)| d)
+        .collect();
+    assert_eq!(lut_ww, [&gray.ww[..], &fast.ww[..]]);
+}
+
+#[test]
+fn rotated_drawing_lands_on_the_panel() {
+    use pico_epd_driver::orientation::{Orientation, Rotation};
+
+    let (hw, mut epd, mut sim) = setup();
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut fb = Framebuffer::new(&mut buf);
+    fb.set_orientation(Orientation::new(Rotation::Deg90));
+    assert_eq!(fb.size(), Size::new(480, 800));
+
+    // Top-left of the portrait screen is the panel's top-right corner.
+    fill(&mut fb, 0, 0, 1, 1);
+    // Off the portrait screen, though inside the landscape panel.
+    fill(&mut fb, 600, 0, 1, 1);
+    let regions = fb.dirty_regions();
+    assert_eq!(regions.len(), 1);
+    assert_eq!(
+        regions[0],
+        Rect {
+            x: 792,
+            y: 0,
+            w: 8,
+            h: 1
+        }
+    );
+
+    assert_eq!(block_on(fb.flush(&mut epd)).unwrap(), Flush::Partial(1));
+    sim.feed(&hw.take_trace());
+    assert!(sim.screen().pixel(799, 0));
+    assert_eq!(sim.screen().data, fb.buffer());
+
+    let mut fb = Framebuffer::new(&mut buf);
+    fb.set_orientation(Orientation::new(Rotation::Deg180).mirrored_x());
+    fill(&mut fb, 10, 20, 1, 1);
+    assert!(fb.buffer()[(479 - 20) * 100 + 1] & 0x20 != 0); // (10, 459)
+}
+
+#[test]
+fn orientation_maps_rects() {
+    use pico_epd_driver::orientation::{Orientation, Rotation};
+
+    let r = Rect {
+        x: 10,
+        y: 20,
+        w: 30,
+        h: 40,
+    };
+    let map = |o: Orientation| o.rect_to_panel(r, 800, 480);
+    assert_eq!(map(Orientation::default()), r);
+    let deg90 = Rect {
+        x: 740,
+        y: 10,
+        w: 40,
+        h: 30,
+    };
+    assert_eq!(map(Orientation::new(Rotation::Deg90)), deg90);
+    let deg180 = Rect {
+        x: 760,
+        y: 420,
+        w: 30,
+        h: 40,
+    };
+    assert_eq!(map(Orientation::new(Rotation::Deg180)), deg180);
+    let deg270 = Rect {
+        x: 20,
+        y: 440,
+        w: 40,
+        h: 30,
+    };
+    assert_eq!(map(Orientation::new(Rotation::Deg270)), deg270);
+    let flipped = Rect {
+        x: 10,
+        y: 420,
+        w: 30,
+        h: 40,
+    };
+    assert_eq!(map(Orientation::default().mirrored_y()), flipped);
+
+    // Every pixel of the drawing area maps to a distinct panel pixel.
+    let o = Orientation::new(Rotation::Deg270).mirrored_x();
+    let (w, h) = o.size(16, 8);
+    let mut seen = [false; 16 * 8];
+    for y in 0..h {
+        for x in 0..w {
+            let (px, py) = o.to_panel(x, y, 16, 8);
+            assert!(!std::mem::replace(&mut seen[py * 16 + px], true));
+        }
+    }
+}
+
+#[test]
+fn portrait_console() {
+    use pico_epd_driver::console::EpdConsole;
+    use pico_epd_driver::orientation::{Orientation, Rotation};
+
+    let (hw, mut epd, mut sim) = setup();
+    let mut buf = vec![0u8; BUF_SIZE];
+    {
+        let mut console = EpdConsole::with_buffer(&mut epd, &mut buf);
+        console.set_orientation(Orientation::new(Rotation::Deg90));
+        block_on(console.show()).unwrap();
+        block_on(console.push("hello")).unwrap();
+        block_on(console.push("world")).unwrap();
+    }
+    let ops = hw.take_trace();
+    // The second line is a column of the panel, padded to whole bytes.
+    let windows: Vec<&[u8]> = ops
+        .iter()
+        .filter_map(|o| o.as_cmd())
+        .filter(|(c, _)| *c == Command::PartialWindow)
+        .map(|(_, d)| d)
+        .collect();
+    // Logical rows 27..45 are panel columns 755..772, widened to 752..775.
+    let want = [0x02, 0xF0, 0x03, 0x07, 0x00, 0x00, 0x01, 0xDF, 0x01];
+    assert_eq!(windows, [&want[..]]);
+
+    sim.feed(&ops);
+    assert_eq!(sim.screen().data, buf);
+    // Text runs down the right-hand edge; the rest is only border.
+    let ink = |x0: usize, x1: usize| (x0..x1).any(|x| (1..479).any(|y| sim.screen().pixel(x, y)));
+    assert!(ink(755, 791));
+    assert!(!ink(1, 755));
+}
+
+#[test]
+fn display_is_a_draw_target() {
+    use embedded_graphics::mono_font::{MonoTextStyle, ascii::FONT_6X10};
+    use embedded_graphics::text::Text;
+    use pico_epd_driver::display::Display;
+
+    let (hw, epd, mut sim) = setup();
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut short = vec![0u8; BUF_SIZE - 1];
+    let err = Display::new(epd, &mut short).err().unwrap();
+    assert_eq!((err.expected, err.got), (BUF_SIZE, BUF_SIZE - 1));
+    let mut display = Display::new(err.epd, &mut buf).unwrap();
+    assert_eq!(display.size(), Size::new(800, 480));
+
+    Text::new(
+        "hi",
+        Point::new(20, 20),
+        MonoTextStyle::new(&FONT_6X10, BinaryColor::On),
+    )
+    .draw(&mut display)
+    .unwrap();
+    assert_eq!(block_on(display.flush()).unwrap(), Flush::Partial(1));
+    sim.feed(&hw.take_trace());
+    assert_eq!(sim.screen().data, display.framebuffer().buffer());
+
+    // Only the requested area goes out; the rest stays pending.
+    Rectangle::new(Point::new(100, 100), Size::new(4, 4))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(&mut display)
+        .unwrap();
+    Rectangle::new(Point::new(300, 300), Size::new(4, 4))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(&mut display)
+        .unwrap();
+    block_on(display.flush_partial(Rectangle::new(Point::new(98, 98), Size::new(8, 8)))).unwrap();
+    sim.feed(&hw.take_trace());
+    assert!(sim.screen().pixel(101, 101) && !sim.screen().pixel(301, 301));
+    let pending = display.framebuffer().dirty_regions();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(
+        pending[0],
+        Rect {
+            x: 296,
+            y: 300,
+            w: 8,
+            h: 4
+        }
+    );
+
+    // Areas off screen are clipped away.
+    block_on(display.flush_partial(Rectangle::new(Point::new(-20, 900), Size::new(10, 10))))
+        .unwrap();
+    assert!(hw.take_trace().is_empty());
+
+    display.clear(BinaryColor::Off).unwrap();
+    block_on(display.flush_full()).unwrap();
+    assert!(!display.framebuffer().is_dirty());
+    // The driver can be wrapped again, e.g. with a fresh buffer.
+    let epd: Epd = display.release();
+    let mut other = vec![0u8; BUF_SIZE];
+    assert!(Display::new(epd, &mut other).is_ok());
+}
@@ -0,0 +1,181 @@
+//! Text and binary LUT encodings, decoding and validation.
+
+use embassy_time::Duration;
+use pico_epd_driver::epd_driver::{
+    DisplayMode, Level, LutError, LutParseError, LutSet, PhaseGroup, TempRange, phase_groups,
+};
+
+const ALL: [DisplayMode; 4] = [
+    DisplayMode::Official,
+    DisplayMode::Fast,
+    DisplayMode::Terminal,
+    DisplayMode::Gray4,
+];
+
+#[test]
+fn text_round_trip() {
+    for mode in ALL {
+        let luts = mode.lut_set();
+        assert_eq!(LutSet::from_text(&luts.to_string()), Ok(luts));
+    }
+}
+
+#[test]
+fn binary_round_trip() {
+    for mode in ALL {
+        let luts = mode.lut_set();
+        let bytes = luts.to_bytes();
+        assert_eq!(&bytes[..4], b"LUT\x01");
+        assert_eq!(LutSet::from_bytes(&bytes), Ok(luts));
+    }
+}
+
+#[test]
+fn text_accepts_comments_prefixes_and_any_order() {
+    let zeros = "00 ".repeat(42);
+    let text = format!(
+        "# hand-tuned\n\
+         bb: {zeros}\n\
+         wb: {zeros}\nbw: {zeros}\nww: {zeros}\n\
+         vcom:  # continues below\n  0x01, 0x02 {}\n\
+         voltage_frame: 0x06 3f 3F 11 24 07 17\n",
+        "00 ".repeat(40)
+    );
+    let luts = LutSet::from_text(&text).unwrap();
+    assert_eq!(
+        luts.voltage_frame,
+        [0x06, 0x3F, 0x3F, 0x11, 0x24, 0x07, 0x17]
+    );
+    assert_eq!(&luts.vcom[..3], &[0x01, 0x02, 0x00]);
+}
+
+#[test]
+fn text_errors() {
+    let good = DisplayMode::Fast.lut_set().to_string();
+
+    assert_eq!(
+        LutSet::from_text(&format!("17\n{good}")),
+        Err(LutParseError::Syntax { line: 1 })
+    );
+    assert_eq!(
+        LutSet::from_text(&format!("{good}lut_xx: 00\n")),
+        Err(LutParseError::UnknownTable { line: 42 })
+    );
+    assert_eq!(
+        LutSet::from_text(&format!("{good}ww: 00\n")),
+        Err(LutParseError::Duplicate { line: 42 })
+    );
+    assert_eq!(
+        LutSet::from_text(&good.replacen("06", "G6", 1)),
+        Err(LutParseError::BadByte { line: 1 })
+    );
+    assert_eq!(
+        LutSet::from_text(&good.replacen("voltage_frame: 06", "voltage_frame:", 1)),
+        Err(LutParseError::WrongSize {
+            table: "voltage_frame",
+            expected: 7,
+            got: 6
+        })
+    );
+    let no_bb = &good[..good.find("bb:").unwrap()];
+    assert_eq!(
+        LutSet::from_text(no_bb),
+        Err(LutParseError::Missing { table: "bb" })
+    );
+}
+
+#[test]
+fn binary_errors() {
+    let mut bytes = DisplayMode::Fast.lut_set().to_bytes();
+    assert_eq!(
+        LutSet::from_bytes(&bytes[..100]),
+        Err(LutParseError::BadLength {
+            expected: LutSet::BIN_LEN,
+            got: 100
+        })
+    );
+    bytes[3] = 2;
+    assert_eq!(LutSet::from_bytes(&bytes), Err(LutParseError::BadMagic));
+}
+
+#[test]
+fn shipped_tables_balance() {
+    for mode in [DisplayMode::Official, DisplayMode::Gray4] {
+        assert_eq!(mode.lut_set().validate(), Ok(()), "{mode:?}");
+    }
+    assert_eq!(DisplayMode::Fast.lut_set().dc_offsets(), [-15, -15, 15, 15]);
+    assert_eq!(
+        DisplayMode::Terminal.lut_set().validate(),
+        Err(LutError::DcImbalance {
+            table: "bw",
+            net: 45
+        })
+    );
+}
+
+#[test]
+fn validate_catches_typos() {
+    let mut luts = DisplayMode::Official.lut_set();
+    // First phase of group 1 of the BW LUT switched to VDHR
+    luts.bw[6] |= 0xC0;
+    assert_eq!(
+        luts.validate(),
+        Err(LutError::RedVoltage {
+            table: "bw",
+            group: 1
+        })
+    );
+
+    // One extra VDL frame in the first phase of the WB LUT
+    let mut luts = DisplayMode::Official.lut_set();
+    luts.wb[1] += 1;
+    assert_eq!(
+        luts.validate(),
+        Err(LutError::DcImbalance {
+            table: "wb",
+            net: -1
+        })
+    );
+}
+
+#[test]
+fn phase_groups_decode() {
+    let g = phase_groups(&DisplayMode::Fast.lut_set().ww);
+    assert_eq!(
+        g[0],
+        PhaseGroup {
+            levels: [Level::Vdl, Level::Gnd, Level::Vdh, Level::Gnd],
+            frames: [0x0F, 0x01, 0x0F, 0x01],
+            repeat: 1,
+        }
+    );
+    assert_eq!(g[0].duration_frames(), 32);
+}
+
+#[test]
+fn cold_ranges_keep_dc_balance() {
+    // Without measured cold tables the room-temperature set is used, so a
+    // cold panel sees no more DC stress than a warm one.
+    for mode in [
+        DisplayMode::Official,
+        DisplayMode::Fast,
+        DisplayMode::Terminal,
+        DisplayMode::Gray4,
+    ] {
+        let normal = mode.lut_set().dc_offsets();
+        for range in [TempRange::Cold, TempRange::Freezing] {
+            assert_eq!(mode.lut_set_for(range).dc_offsets(), normal);
+        }
+    }
+}
+
+#[test]
+fn refresh_time_estimate() {
+    let fast = DisplayMode::Fast.lut_set();
+    assert_eq!(fast.duration_frames(), 62);
+    assert_eq!(
+        fast.estimated_refresh_time(0x06),
+        Some(Duration::from_millis(1240))
+    );
+    assert_eq!(fast.estimated_refresh_time(0x0F), None);
+}
@@ -0,0 +1,780 @@
+//! Golden command transcripts recorded through the mock bus.
+
+use embassy_futures::block_on;
+use embassy_time::Duration;
+use pico_epd_driver::epd_driver::mock::{Busy, MockBusy, MockHw, MockPin, MockSpi, Op};
+use pico_epd_driver::epd_driver::{
+    BUF_SIZE, Border, BusyOp, Command, DisplayMode, Epd800x480, EpdBus, EpdBusError, EpdConfig,
+    EpdDriverError, FrameRate, Panel5in83V2, Rect, RectError, RefreshPolicy, TempRange, Timeouts,
+};
+
+type Epd = Epd800x480<MockSpi, MockPin, MockPin, MockPin, MockBusy, MockPin>;
+
+fn setup() -> (MockHw, Epd) {
+    let hw = MockHw::new();
+    let epd = Epd800x480::new(hw.bus(), hw.led());
+    (hw, epd)
+}
+
+fn cmd(c: Command, d: &[u8]) -> Op {
+    Op::Cmd(c, d.to_vec())
+}
+
+/// `wait_ready` polls the status register before blocking on BUSY.
+fn ready() -> [Op; 2] {
+    [cmd(Command::GetStatus, &[]), Op::WaitBusy]
+}
+
+#[test]
+fn init_transcript() {
+    let (hw, mut epd) = setup();
+    block_on(epd.init()).unwrap();
+
+    let mut want = vec![Op::Reset];
+    want.extend(ready());
+    want.extend([
+        cmd(Command::PowerSetting, &[0x17, 0x17, 0x3f, 0x3f, 0x11]),
+        cmd(Command::VcomDc, &[0x24]),
+        cmd(Command::Btst, &[0x27, 0x27, 0x2F, 0x17]),
+        cmd(Command::Pll, &[0x06]),
+        cmd(Command::PowerOn, &[]),
+    ]);
+    want.extend(ready());
+    want.extend([
+        cmd(Command::PanelSetting, &[0x1F]),
+        cmd(Command::TRes, &[0x03, 0x20, 0x01, 0xE0]),
+        cmd(Command::DualSPI, &[0x00]),
+        cmd(Command::VcomAndDataInterval, &[0x10, 0x07]),
+        cmd(Command::TconSetting, &[0x22]),
+    ]);
+    assert_eq!(hw.trace(), want);
+    assert!(!hw.led_is_on());
+}
+
+#[test]
+fn init_config_overrides() {
+    let (hw, mut epd) = setup();
+    block_on(epd.init_with(EpdConfig::default())).unwrap();
+    let default = hw.take_trace();
+    block_on(epd.init()).unwrap();
+    assert_eq!(hw.take_trace(), default);
+
+    let config = EpdConfig::new()
+        .vcom_mv(-2000)
+        .frame_rate(FrameRate::Hz100)
+        .border(Border::Floating);
+    block_on(epd.init_with(config)).unwrap();
+    let ops = hw.take_trace();
+    let sent = |c: Command| {
+        ops.iter()
+            .filter_map(|o| o.as_cmd())
+            .find(|(cmd, _)| *cmd == c)
+            .map(|(_, d)| d.to_vec())
+            .unwrap()
+    };
+    assert_eq!(sent(Command::VcomDc), [0x26]);
+    assert_eq!(sent(Command::Pll), [0x0B]);
+    assert_eq!(sent(Command::Btst), [0x27, 0x27, 0x2F, 0x17]);
+    assert_eq!(sent(Command::VcomAndDataInterval), [0x80, 0x07]);
+
+    // Kept for the differential bit and later inits.
+    assert_eq!(epd.config(), config);
+    block_on(epd.set_mode(DisplayMode::Terminal)).unwrap();
+    hw.take_trace();
+    block_on(epd.set_differential(true)).unwrap();
+    assert_eq!(
+        hw.take_trace(),
+        [cmd(Command::VcomAndDataInterval, &[0x88, 0x07])]
+    );
+}
+
+#[test]
+fn init_config_adds_registers_the_panel_lacks() {
+    let hw = MockHw::new();
+    let mut epd =
+        pico_epd_driver::epd_driver::Epd::<Panel5in83V2, _, _, _, _, _, _>::new(hw.bus(), hw.led());
+    block_on(epd.init_with(EpdConfig::new().vcom_mv(-1900))).unwrap();
+
+    let cmds: Vec<(Command, Vec<u8>)> = hw
+        .trace()
+        .iter()
+        .filter_map(|o| o.as_cmd())
+        .map(|(c, d)| (c, d.to_vec()))
+        .take_while(|(c, _)| *c != Command::PowerOn)
+        .filter(|(c, _)| *c != Command::GetStatus)
+        .collect();
+    assert_eq!(
+        cmds,
+        [
+            (Command::PowerSetting, vec![0x07, 0x07, 0x3f, 0x3f]),
+            (Command::VcomDc, vec![0x24]),
+        ]
+    );
+}
+
+#[test]
+fn init_config_overrides_power_tcon_and_interval() {
+    use pico_epd_driver::epd_driver::{PanelSpec, Uc8179Panel};
+
+    fn sent(hw: &MockHw, c: Command) -> Vec<u8> {
+        hw.trace()
+            .iter()
+            .filter_map(|o| o.as_cmd())
+            .find(|(cmd, _)| *cmd == c)
+            .map(|(_, d)| d.to_vec())
+            .unwrap()
+    }
+
+    let config = EpdConfig::new()
+        .power([0x07, 0x3A, 0x3A])
+        .tcon(0x33)
+        .data_interval(0x19)
+        .border(Border::Black);
+    let (hw, mut epd) = setup();
+    block_on(epd.init_with(config)).unwrap();
+    assert_eq!(
+        sent(&hw, Command::PowerSetting),
+        [0x17, 0x07, 0x3A, 0x3A, 0x11]
+    );
+    assert_eq!(sent(&hw, Command::TconSetting), [0x33]);
+    assert_eq!(sent(&hw, Command::VcomAndDataInterval), [0x20, 0x09]);
+
+    // A panel with DDX = 01 inverts the data and the border levels.
+    struct Inverted;
+    impl PanelSpec for Inverted {
+        const WIDTH: usize = 800;
+        const HEIGHT: usize = 480;
+    }
+    impl Uc8179Panel for Inverted {
+        const POWER_SEQUENCE: &'static [(Command, &'static [u8])] = &[];
+        const PANEL_SETTING: u8 = 0x1F;
+        const PANEL_SEQUENCE: &'static [(Command, &'static [u8])] =
+            &[(Command::VcomAndDataInterval, &[0x21, 0x07])];
+    }
+    let hw = MockHw::new();
+    let mut epd =
+        pico_epd_driver::epd_driver::Epd::<Inverted, _, _, _, _, _, _>::new(hw.bus(), hw.led());
+    block_on(epd.init_with(config.border(Border::White))).unwrap();
+    assert_eq!(sent(&hw, Command::VcomAndDataInterval), [0x21, 0x09]);
+    // Registers the panel does not send are added with internal supplies
+    // resp. on their own.
+    assert_eq!(sent(&hw, Command::PowerSetting), [0x07, 0x07, 0x3A, 0x3A]);
+    assert_eq!(sent(&hw, Command::TconSetting), [0x33]);
+}
+
+#[test]
+fn display_transcript() {
+    let (hw, mut epd) = setup();
+    let buf: Vec<u8> = (0..BUF_SIZE).map(|i| i as u8).collect();
+    block_on(epd.display(&buf)).unwrap();
+
+    let mut want = ready().to_vec();
+    want.extend([
+        cmd(Command::DataStartTransmission1, &[0u8; BUF_SIZE]),
+        cmd(Command::DataStartTransmission2, &buf),
+        cmd(Command::DisplayRefresh, &[]),
+    ]);
+    want.extend(ready());
+    assert_eq!(hw.trace(), want);
+}
+
+#[test]
+fn streamed_frames_match_display() {
+    let (hw, mut epd) = setup();
+    let mut frame = vec![0u8; BUF_SIZE];
+    for (i, b) in frame.iter_mut().enumerate() {
+        *b = (i * 7 % 251) as u8;
+    }
+    block_on(epd.display(&frame)).unwrap();
+    let want = hw.take_trace();
+
+    block_on(epd.display_rows(|y, row| row.copy_from_slice(&frame[y * 100..(y + 1) * 100])))
+        .unwrap();
+    assert_eq!(hw.take_trace(), want);
+
+    let pixels = frame
+        .iter()
+        .flat_map(|&b| (0..8).map(move |bit| b & (0x80 >> bit) != 0));
+    block_on(epd.display_iter(pixels)).unwrap();
+    assert_eq!(hw.take_trace(), want);
+
+    // Running out of pixels leaves the rest of the frame white.
+    block_on(epd.display_iter([true; 12])).unwrap();
+    let dtm2 = hw
+        .take_trace()
+        .into_iter()
+        .find_map(|o| match o {
+            Op::Cmd(Command::DataStartTransmission2, d) => Some(d),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(dtm2.len(), BUF_SIZE);
+    assert_eq!(dtm2[..2], [0xFF, 0xF0]);
+    assert!(dtm2[2..].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn display_rejects_bad_len() {
+    let (_hw, mut epd) = setup();
+    assert!(block_on(epd.display(&[0u8; 10])).is_err());
+}
+
+#[test]
+fn display_partial_transcript() {
+    let (hw, mut epd) = setup();
+    let r = Rect {
+        x: 16,
+        y: 300,
+        w: 64,
+        h: 2,
+    };
+    let buf = [0xAAu8; 16];
+    block_on(epd.display_partial(&buf, r)).unwrap();
+
+    let mut want = ready().to_vec();
+    want.extend([
+        cmd(Command::PartialIn, &[]),
+        cmd(
+            Command::PartialWindow,
+            &[0x00, 16, 0x00, 79, 0x01, 0x2C, 0x01, 0x2D, 0x01],
+        ),
+        cmd(Command::DataStartTransmission2, &buf),
+        cmd(Command::DisplayRefresh, &[]),
+    ]);
+    want.extend(ready());
+    want.push(cmd(Command::PartialOut, &[]));
+    assert_eq!(hw.trace(), want);
+}
+
+#[test]
+fn display_partial_rejects_bad_windows() {
+    let (hw, mut epd) = setup();
+    let partial = |epd: &mut Epd, buf: &[u8], x, y, w, h| {
+        block_on(epd.display_partial(buf, Rect { x, y, w, h })).unwrap_err()
+    };
+    assert!(matches!(
+        partial(&mut epd, &[], 0, 0, 0, 10),
+        EpdDriverError::EmptyRect
+    ));
+    assert!(matches!(
+        partial(&mut epd, &[0; 16], 800, 0, 8, 2),
+        EpdDriverError::RectOutOfBounds
+    ));
+    assert!(matches!(
+        partial(&mut epd, &[0; 16], 0, 479, 8, 2),
+        EpdDriverError::RectOutOfBounds
+    ));
+    assert!(matches!(
+        partial(&mut epd, &[0; 16], usize::MAX - 7, 0, 8, 2),
+        EpdDriverError::RectOutOfBounds
+    ));
+    assert!(matches!(
+        partial(&mut epd, &[0; 16], 4, 0, 64, 2),
+        EpdDriverError::Misaligned
+    ));
+    assert!(matches!(
+        partial(&mut epd, &[0; 15], 16, 0, 64, 2),
+        EpdDriverError::BadBufferLen {
+            expected: 16,
+            got: 15
+        }
+    ));
+    let fb = [0u8; BUF_SIZE];
+    let r = Rect {
+        x: 8,
+        y: 0,
+        w: 12,
+        h: 1,
+    };
+    assert!(matches!(
+        block_on(epd.display_region(&fb, r)),
+        Err(EpdDriverError::Misaligned)
+    ));
+    assert!(matches!(
+        block_on(epd.display(&fb[1..])),
+        Err(EpdDriverError::BadBufferLen { .. })
+    ));
+    assert!(matches!(
+        block_on(epd.display_diff(&fb, &fb[1..])),
+        Err(EpdDriverError::BadBufferLen { .. })
+    ));
+    assert!(hw.trace().is_empty());
+
+    assert_eq!(
+        Rect::new::<Panel5in83V2>(648, 0, 8, 1),
+        Err(RectError::OutOfBounds)
+    );
+    assert_eq!(
+        Rect::new::<Panel5in83V2>(640, 472, 8, 8).map(|r| r.buf_len()),
+        Ok(8)
+    );
+}
+
+#[test]
+fn clear_transcript() {
+    let (hw, mut epd) = setup();
+    block_on(epd.clear()).unwrap();
+
+    let mut want = ready().to_vec();
+    want.extend([
+        cmd(Command::DataStartTransmission1, &[0u8; BUF_SIZE]),
+        cmd(Command::DataStartTransmission2, &[0u8; BUF_SIZE]),
+        cmd(Command::DisplayRefresh, &[]),
+    ]);
+    want.extend(ready());
+    assert_eq!(hw.trace(), want);
+}
+
+#[test]
+fn sleep_transcript() {
+    let (hw, mut epd) = setup();
+    block_on(epd.sleep()).unwrap();
+
+    let mut want = vec![cmd(Command::PowerOff, &[])];
+    want.extend(ready());
+    want.push(cmd(Command::DeepSleep, &[0xA5]));
+    assert_eq!(hw.trace(), want);
+}
+
+#[test]
+fn set_mode_loads_lut_registers() {
+    let (hw, mut epd) = setup();
+    block_on(epd.set_mode(DisplayMode::Fast)).unwrap();
+
+    let ops = hw.take_trace();
+    let cmds: Vec<Command> = ops
+        .iter()
+        .filter_map(|o| o.as_cmd())
+        .map(|(c, _)| c)
+        .collect();
+    assert_eq!(
+        cmds,
+        [
+            Command::PanelSetting,
+            Command::Btst,
+            Command::Vcom,
+            Command::LutWw,
+            Command::LutBw,
+            Command::LutWb,
+            Command::LutBb,
+        ]
+    );
+    assert_eq!(ops[0], cmd(Command::PanelSetting, &[0x3F]));
+    for (_, data) in ops.iter().skip(2).filter_map(|o| o.as_cmd()) {
+        assert_eq!(data.len(), 42);
+    }
+    let luts = DisplayMode::Fast.lut_set();
+    assert_eq!(ops[1], cmd(Command::Btst, &luts.voltage_frame));
+    assert_eq!(ops[3], cmd(Command::LutWw, &luts.ww));
+}
+
+fn short_timeouts() -> Timeouts {
+    let t = Duration::from_millis(50);
+    Timeouts {
+        reset: t,
+        power_on: t,
+        refresh: t,
+        power_off: t,
+    }
+}
+
+#[test]
+fn dead_panel_times_out() {
+    let (hw, mut epd) = setup();
+    epd.set_timeouts(short_timeouts());
+    hw.script_busy(Busy::Never);
+
+    match block_on(epd.sleep()) {
+        Err(EpdDriverError::Timeout { op }) => assert_eq!(op, BusyOp::PowerOff),
+        other => panic!("expected timeout, got {other:?}"),
+    }
+    assert!(!hw.trace().contains(&cmd(Command::DeepSleep, &[0xA5])));
+}
+
+#[test]
+fn slow_panel_within_timeout() {
+    let (hw, mut epd) = setup();
+    epd.set_timeouts(short_timeouts());
+    hw.script_busy(Busy::After(Duration::from_millis(10)));
+    block_on(epd.sleep()).unwrap();
+}
+
+#[test]
+fn timeout_recovers_with_reset_and_init() {
+    let (hw, mut epd) = setup();
+    epd.set_timeouts(short_timeouts());
+    epd.set_auto_recover(true);
+    block_on(epd.set_mode(DisplayMode::Terminal)).unwrap();
+    hw.take_trace();
+    hw.script_busy(Busy::Never);
+
+    let err = block_on(epd.clear()).unwrap_err();
+    assert!(matches!(
+        err,
+        EpdDriverError::Timeout {
+            op: BusyOp::Refresh
+        }
+    ));
+
+    let ops = hw.trace();
+    let reset = ops.iter().position(|o| *o == Op::Reset).expect("no reset");
+    let rest: Vec<Command> = ops[reset..]
+        .iter()
+        .filter_map(|o| o.as_cmd())
+        .map(|(c, _)| c)
+        .collect();
+    assert!(rest.contains(&Command::PowerOn));
+    assert_eq!(rest.last(), Some(&Command::LutBb));
+}
+
+#[test]
+fn init_reloads_the_loaded_mode() {
+    let (hw, mut epd) = setup();
+    block_on(epd.init()).unwrap();
+    block_on(epd.set_mode(DisplayMode::Fast)).unwrap();
+    block_on(epd.init()).unwrap();
+    block_on(epd.display(&[0u8; BUF_SIZE])).unwrap();
+
+    let ops = hw.trace();
+    let reset = ops.iter().rposition(|o| *o == Op::Reset).unwrap();
+    let after: Vec<(Command, &[u8])> = ops[reset..].iter().filter_map(|o| o.as_cmd()).collect();
+    let fast = DisplayMode::Fast.lut_set();
+    let lut = |c| after.iter().find(|(cmd, _)| *cmd == c).map(|(_, d)| *d);
+    assert_eq!(lut(Command::LutWw), Some(&fast.ww[..]));
+    assert_eq!(lut(Command::LutBb), Some(&fast.bb[..]));
+    let panel_settings: Vec<u8> = after
+        .iter()
+        .filter(|(c, _)| *c == Command::PanelSetting)
+        .map(|(_, d)| d[0])
+        .collect();
+    assert_eq!(panel_settings.last(), Some(&(0x1F | 0x20)));
+    let lut_at = after.iter().position(|(c, _)| *c == Command::LutBb);
+    let dtm2_at = after
+        .iter()
+        .position(|(c, _)| *c == Command::DataStartTransmission2);
+    assert!(lut_at < dtm2_at);
+}
+
+#[test]
+fn set_lut_loads_custom_tables_and_survives_clean() {
+    let (hw, mut epd) = setup();
+    let mut luts = DisplayMode::Fast.lut_set();
+    luts.ww[0] = 0x48;
+    block_on(epd.set_lut(&luts)).unwrap();
+
+    let ops = hw.take_trace();
+    assert_eq!(ops[0], cmd(Command::PanelSetting, &[0x3F]));
+    assert_eq!(ops[1], cmd(Command::Btst, &luts.voltage_frame));
+    assert_eq!(ops[3], cmd(Command::LutWw, &luts.ww));
+
+    // `clean` drives the Official waveform, then reloads the custom set.
+    block_on(epd.clean()).unwrap();
+    let ops = hw.take_trace();
+    let ww: Vec<&[u8]> = ops
+        .iter()
+        .filter_map(|o| o.as_cmd())
+        .filter(|(c, _)| *c == Command::LutWw)
+        .map(|(_, d)| d)
+        .collect();
+    assert_eq!(ww, [&DisplayMode::Official.lut_set().ww[..], &luts.ww[..]]);
+
+    // Temperature changes leave a custom set alone.
+    block_on(epd.set_temperature(-5)).unwrap();
+    assert!(hw.take_trace().is_empty());
+}
+
+#[test]
+fn read_status_and_temperature() {
+    let (hw, mut epd) = setup();
+    hw.script_read(&[0x25, 0xF6, 0x80]);
+
+    let status = block_on(epd.read_status()).unwrap();
+    assert!(!status.is_busy());
+    assert!(status.is_powered());
+    assert!(status.i2c_error());
+    assert_eq!(block_on(epd.read_temperature()).unwrap(), -10);
+
+    assert_eq!(
+        hw.trace(),
+        [
+            cmd(Command::GetStatus, &[]),
+            Op::Read(vec![0x25]),
+            cmd(Command::Tsc, &[]),
+            Op::Read(vec![0xF6, 0x80]),
+        ]
+    );
+}
+
+#[test]
+fn temperature_bucket_change_reloads_luts() {
+    let (hw, mut epd) = setup();
+    block_on(epd.set_mode(DisplayMode::Fast)).unwrap();
+    hw.take_trace();
+
+    // Same bucket: nothing is sent.
+    block_on(epd.set_temperature(25)).unwrap();
+    assert!(hw.take_trace().is_empty());
+
+    block_on(epd.set_temperature(4)).unwrap();
+    let cold = DisplayMode::Fast.lut_set_for(TempRange::Cold);
+    let ops = hw.take_trace();
+    assert!(ops.contains(&cmd(Command::LutWw, &cold.ww)));
+
+    hw.script_read(&[20, 0]);
+    assert_eq!(block_on(epd.update_temperature()).unwrap(), 20);
+    let ops = hw.take_trace();
+    assert!(ops.contains(&cmd(Command::LutWw, &DisplayMode::Fast.lut_set().ww)));
+}
+
+#[test]
+fn policy_inserts_official_cleaning_refresh() {
+    let (hw, mut epd) = setup();
+    epd.set_refresh_policy(RefreshPolicy {
+        max_partials: Some(3),
+        ..Default::default()
+    });
+    block_on(epd.set_mode(DisplayMode::Terminal)).unwrap();
+    let r = Rect {
+        x: 0,
+        y: 0,
+        w: 8,
+        h: 1,
+    };
+    let refreshes = |ops: &[Op]| {
+        ops.iter()
+            .filter(|o| matches!(o.as_cmd(), Some((Command::DisplayRefresh, _))))
+            .count()
+    };
+
+    for _ in 0..2 {
+        block_on(epd.display_partial(&[0xFF], r)).unwrap();
+    }
+    hw.take_trace();
+    block_on(epd.display_partial(&[0xFF], r)).unwrap();
+    let ops = hw.take_trace();
+    assert_eq!(refreshes(&ops), 2);
+
+    let official = DisplayMode::Official.lut_set();
+    let terminal = DisplayMode::Terminal.lut_set();
+    let out = ops
+        .iter()
+        .position(|o| *o == cmd(Command::PartialOut, &[]))
+        .unwrap();
+    let tail = &ops[out..];
+    let load = tail
+        .iter()
+        .position(|o| *o == cmd(Command::LutWw, &official.ww))
+        .expect("official LUT not loaded");
+    let refresh = tail
+        .iter()
+        .position(|o| *o == cmd(Command::DisplayRefresh, &[]))
+        .unwrap();
+    let restore = tail
+        .iter()
+        .position(|o| *o == cmd(Command::LutWw, &terminal.ww))
+        .expect("terminal LUT not restored");
+    assert!(load < refresh && refresh < restore);
+
+    // Counters restart after cleaning.
+    block_on(epd.display_partial(&[0xFF], r)).unwrap();
+    assert_eq!(refreshes(&hw.take_trace()), 1);
+}
+
+#[test]
+fn gray_frames_count_and_restore_the_old_plane() {
+    use pico_epd_driver::epd_driver::GRAY_BUF_SIZE;
+    use pico_epd_driver::epd_driver::sim::PanelSim;
+
+    let (hw, mut epd) = setup();
+    block_on(epd.init()).unwrap();
+    block_on(epd.set_mode(DisplayMode::Terminal)).unwrap();
+    block_on(epd.set_differential(true)).unwrap();
+    epd.set_refresh_policy(RefreshPolicy {
+        max_partials: Some(2),
+        ..Default::default()
+    });
+    let refreshes = |ops: &[Op]| {
+        ops.iter()
+            .filter(|o| matches!(o.as_cmd(), Some((Command::DisplayRefresh, _))))
+            .count()
+    };
+    // Black, dark gray, light gray, white in the first byte pair.
+    let mut gray = vec![0xFFu8; GRAY_BUF_SIZE];
+    gray[0] = 0b0001_1011;
+    let mut sim = PanelSim::new();
+    sim.feed(&hw.take_trace());
+
+    block_on(epd.display_gray(&gray)).unwrap();
+    let ops = hw.take_trace();
+    assert_eq!(refreshes(&ops), 1);
+    // After the refresh, DTM1 gets the high-bit plane back.
+    let refresh = ops
+        .iter()
+        .position(|o| *o == cmd(Command::DisplayRefresh, &[]))
+        .unwrap();
+    let dtm1 = ops[refresh..]
+        .iter()
+        .find_map(|o| match o.as_cmd() {
+            Some((Command::DataStartTransmission1, d)) => Some(d.to_vec()),
+            _ => None,
+        })
+        .expect("old plane not rewritten");
+    assert_eq!(dtm1[0], 0b1100_0000);
+    assert!(dtm1[1..].iter().all(|&b| b == 0));
+
+    // The second one reaches `max_partials` but is left on screen: a
+    // cleaning refresh over the gray planes would show only the low bit.
+    sim.feed(&ops);
+    block_on(epd.display_gray(&gray)).unwrap();
+    let ops = hw.take_trace();
+    assert_eq!(refreshes(&ops), 1);
+    sim.feed(&ops);
+    assert_eq!(sim.frames().len(), 2);
+    assert_eq!(sim.old_plane()[0], 0b1100_0000);
+
+    // The next 1bpp frame is shown, then cleaned with the frame in RAM.
+    let mut frame = vec![0u8; BUF_SIZE];
+    frame[0] = 0xA5;
+    block_on(epd.display(&frame)).unwrap();
+    let ops = hw.take_trace();
+    assert_eq!(refreshes(&ops), 2);
+    sim.feed(&ops);
+    let frames = sim.frames();
+    assert_eq!(frames.len(), 4);
+    assert!(frames[2..].iter().all(|f| f.data == frame));
+    let terminal = DisplayMode::Terminal.lut_set();
+    assert_eq!(
+        ops.iter()
+            .rev()
+            .find(|o| matches!(o.as_cmd(), Some((Command::LutWw, _)))),
+        Some(&cmd(Command::LutWw, &terminal.ww))
+    );
+}
+
+#[test]
+fn blocking_driver_sends_the_same_sequences() {
+    use pico_epd_driver::epd_driver::blocking::Blocking;
+
+    let (hw, mut epd) = setup();
+    let buf: Vec<u8> = (0..BUF_SIZE).map(|i| (i * 3) as u8).collect();
+    let r = Rect {
+        x: 16,
+        y: 8,
+        w: 16,
+        h: 2,
+    };
+    block_on(async {
+        epd.init().await?;
+        epd.display(&buf).await?;
+        epd.display_partial(&[0xAA; 4], r).await?;
+        epd.sleep().await
+    })
+    .unwrap();
+    let want: Vec<Op> = hw
+        .trace()
+        .into_iter()
+        .filter(|o| *o != Op::WaitBusy)
+        .collect();
+
+    let hw = MockHw::new();
+    let mut epd = Blocking::new(Epd800x480::new(hw.blocking_bus(), hw.led()));
+    epd.init().unwrap();
+    epd.display(&buf).unwrap();
+    epd.display_partial(&[0xAA; 4], r).unwrap();
+    epd.sleep().unwrap();
+    assert_eq!(hw.trace(), want);
+    // reset pulse (20 + 2 + 20 ms) plus the 20 ms status delay per wait
+    assert!(hw.delayed() >= Duration::from_millis(42));
+}
+
+#[test]
+fn blocking_dead_panel_times_out() {
+    use pico_epd_driver::epd_driver::blocking::Blocking;
+
+    let hw = MockHw::new();
+    let mut epd = Blocking::new(Epd800x480::new(hw.blocking_bus(), hw.led()));
+    epd.inner_mut().set_timeouts(short_timeouts());
+    hw.script_busy(Busy::Never);
+
+    match epd.sleep() {
+        Err(EpdDriverError::Timeout { op }) => assert_eq!(op, BusyOp::PowerOff),
+        other => panic!("expected timeout, got {other:?}"),
+    }
+    // 20 ms status delay, then polled until the 50 ms timeout
+    assert_eq!(hw.delayed(), Duration::from_millis(70));
+}
+
+#[test]
+fn shared_bus_device_sends_the_same_sequences() {
+    let buf: Vec<u8> = (0..BUF_SIZE).map(|i| (i * 7) as u8).collect();
+    let r = Rect {
+        x: 16,
+        y: 8,
+        w: 16,
+        h: 2,
+    };
+
+    let (hw, mut epd) = setup();
+    hw.script_read(&[0x25]);
+    block_on(async {
+        epd.init().await?;
+        epd.display(&buf).await?;
+        epd.display_partial(&[0x55; 4], r).await?;
+        epd.read_status().await?;
+        epd.sleep().await
+    })
+    .unwrap();
+
+    let shared = MockHw::new();
+    shared.script_read(&[0x25]);
+    let mut epd = Epd800x480::new(shared.device_bus(), shared.led());
+    block_on(async {
+        epd.init().await?;
+        epd.display(&buf).await?;
+        epd.display_partial(&[0x55; 4], r).await?;
+        epd.read_status().await?;
+        epd.sleep().await
+    })
+    .unwrap();
+    assert_eq!(shared.trace(), hw.trace());
+    assert!(!shared.cs_is_low());
+}
+
+/// A reset line on an I/O expander with its own error type.
+struct ExpanderPin;
+
+#[derive(Debug, PartialEq)]
+struct ExpanderError;
+
+impl embedded_hal::digital::Error for ExpanderError {
+    fn kind(&self) -> embedded_hal::digital::ErrorKind {
+        embedded_hal::digital::ErrorKind::Other
+    }
+}
+
+impl embedded_hal::digital::ErrorType for ExpanderPin {
+    type Error = ExpanderError;
+}
+
+impl embedded_hal::digital::OutputPin for ExpanderPin {
+    fn set_low(&mut self) -> Result<(), ExpanderError> {
+        Err(ExpanderError)
+    }
+
+    fn set_high(&mut self) -> Result<(), ExpanderError> {
+        Ok(())
+    }
+}
+
+#[test]
+fn pin_errors_name_the_failing_line() {
+    let hw = MockHw::new();
+    let bus = EpdBus::new(hw.spi(), hw.cs(), hw.dc(), ExpanderPin, hw.busy());
+    let mut epd = Epd800x480::new(bus, hw.led());
+
+    match block_on(epd.init()) {
+        Err(EpdDriverError::Bus(EpdBusError::Rst(e))) => assert_eq!(e, ExpanderError),
+        other => panic!("expected a reset line error, got {other:?}"),
+    }
+    assert!(hw.trace().is_empty());
+}
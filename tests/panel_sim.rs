@@ -0,0 +1,337 @@
+//! Drive the panel simulator through the mock bus and check the shown frames.
+
+use bitvec::prelude::*;
+use embassy_futures::block_on;
+use pico_epd_driver::epd_driver::mock::{MockBusy, MockHw, MockPin, MockSpi, Op};
+use pico_epd_driver::epd_driver::sim::PanelSim;
+use pico_epd_driver::epd_driver::{
+    BUF_SIZE, Command, DisplayMode, Epd800x480, EpdDriverError, HEIGHT, Rect, WIDTH,
+};
+use pico_epd_driver::ui::{PackError, pack_bitmap, pack_bitmap_onto};
+
+type Epd = Epd800x480<MockSpi, MockPin, MockPin, MockPin, MockBusy, MockPin>;
+
+fn setup() -> (MockHw, Epd, PanelSim) {
+    let hw = MockHw::new();
+    let mut epd = Epd800x480::new(hw.bus(), hw.led());
+    block_on(epd.init()).unwrap();
+    let mut sim = PanelSim::new();
+    sim.feed(&hw.take_trace());
+    (hw, epd, sim)
+}
+
+#[test]
+fn full_refresh_shows_new_plane() {
+    let (hw, mut epd, mut sim) = setup();
+    let buf: Vec<u8> = (0..BUF_SIZE).map(|i| (i * 7) as u8).collect();
+    block_on(epd.display(&buf)).unwrap();
+    sim.feed(&hw.take_trace());
+
+    assert_eq!(sim.frames().len(), 1);
+    assert_eq!(sim.screen().data, buf);
+    assert!(sim.old_plane().iter().all(|&b| b == 0));
+}
+
+#[test]
+fn partial_refresh_only_touches_window() {
+    let (hw, mut epd, mut sim) = setup();
+    block_on(epd.clear()).unwrap();
+
+    let mut raw = [0xFFu8; 25];
+    let bits = &raw.view_bits_mut::<Msb0>()[..20 * 10];
+    let (rect, packed) = pack_bitmap(bits, 42, 100, 20, 10).unwrap();
+    block_on(epd.display_partial(&packed, rect)).unwrap();
+    sim.feed(&hw.take_trace());
+
+    assert!(!sim.in_partial());
+    assert_eq!(sim.frames().len(), 2);
+    let screen = sim.screen();
+    for y in 95..115 {
+        for x in 30..70 {
+            let inside = (42..62).contains(&x) && (100..110).contains(&y);
+            assert_eq!(screen.pixel(x, y), inside, "pixel ({x}, {y})");
+        }
+    }
+}
+
+#[test]
+fn unaligned_window_keeps_neighbours() {
+    let (hw, mut epd, mut sim) = setup();
+    let frame = vec![0xFFu8; BUF_SIZE];
+    block_on(epd.display(&frame)).unwrap();
+    sim.feed(&hw.take_trace());
+
+    // A white 5x3 window at x = 13, inside an all-black screen.
+    let raw = [0u8; 2];
+    let bits = &raw.view_bits::<Msb0>()[..15];
+    let (rect, packed) = pack_bitmap_onto(&frame, WIDTH, HEIGHT, bits, 13, 7, 5, 3).unwrap();
+    assert_eq!((rect.x, rect.w), (8, 16));
+    assert_eq!(&packed[..2], &[0b1111_1000, 0b0011_1111]);
+    block_on(epd.display_partial(&packed, rect)).unwrap();
+    sim.feed(&hw.take_trace());
+    for x in 0..24 {
+        let white = (13..18).contains(&x);
+        assert_eq!(sim.screen().pixel(x, 8), !white, "pixel ({x}, 8)");
+    }
+
+    assert_eq!(
+        pack_bitmap_onto(&frame, WIDTH, HEIGHT, bits, WIDTH - 4, 7, 5, 3).unwrap_err(),
+        PackError::OutOfBounds
+    );
+    assert_eq!(
+        pack_bitmap_onto(&frame, WIDTH, HEIGHT, bits, 0, HEIGHT - 2, 5, 3).unwrap_err(),
+        PackError::OutOfBounds
+    );
+    assert_eq!(
+        pack_bitmap_onto(&frame[1..], WIDTH, HEIGHT, bits, 0, 0, 5, 3).unwrap_err(),
+        PackError::FrameTooSmall {
+            needed: BUF_SIZE,
+            got: BUF_SIZE - 1
+        }
+    );
+
+    // A frame whose rows end mid-byte: 13 pixels in 2 bytes per row.
+    let small = [0xFFu8; 2 * 4];
+    let (rect, packed) = pack_bitmap_onto(&small, 13, 4, bits, 8, 1, 5, 3).unwrap();
+    assert_eq!((rect.x, rect.w), (8, 8));
+    assert_eq!(&packed[..], &[0b0000_0111; 3]);
+
+    // The driver still refuses an unaligned window passed as is.
+    let (_, white) = pack_bitmap(bits, 8, 7, 5, 3).unwrap();
+    let unaligned = Rect {
+        x: 13,
+        y: 7,
+        w: 5,
+        h: 3,
+    };
+    assert!(matches!(
+        block_on(epd.display_partial(&white, unaligned)),
+        Err(EpdDriverError::Misaligned)
+    ));
+}
+
+#[test]
+fn exports_pbm_and_png() {
+    let (hw, mut epd, mut sim) = setup();
+    let mut buf = vec![0u8; BUF_SIZE];
+    buf[0] = 0x80;
+    block_on(epd.display(&buf)).unwrap();
+    sim.feed(&hw.take_trace());
+
+    let mut pbm = Vec::new();
+    sim.screen().write_pbm(&mut pbm).unwrap();
+    let header = format!("P4\n{WIDTH} {HEIGHT}\n");
+    assert!(pbm.starts_with(header.as_bytes()));
+    assert_eq!(pbm.len(), header.len() + BUF_SIZE);
+
+    let mut png = Vec::new();
+    sim.screen().write_png(&mut png).unwrap();
+    assert_eq!(&png[1..4], b"PNG");
+}
+
+#[test]
+fn differential_refresh_sends_only_the_new_plane() {
+    let (hw, mut epd, mut sim) = setup();
+    block_on(epd.set_mode(DisplayMode::Terminal)).unwrap();
+    block_on(epd.set_differential(true)).unwrap();
+    block_on(epd.clear()).unwrap();
+
+    let mut frame = vec![0u8; BUF_SIZE];
+    frame[10] = 0xFF;
+    block_on(epd.display(&frame)).unwrap();
+    frame[20] = 0x0F;
+    block_on(epd.display(&frame)).unwrap();
+
+    let ops = hw.take_trace();
+    let dtm1 = ops
+        .iter()
+        .filter(|o| matches!(o.as_cmd(), Some((Command::DataStartTransmission1, _))))
+        .count();
+    assert_eq!(dtm1, 1, "only `clear` sends the old plane");
+
+    sim.feed(&ops);
+    assert_eq!(sim.frames().len(), 3);
+    assert_eq!(sim.last_driven(), 4);
+    assert_eq!(sim.screen().data, frame);
+    assert_eq!(sim.old_plane(), &frame[..]);
+}
+
+#[test]
+fn differential_mode_needs_transition_luts() {
+    let (hw, mut epd, mut sim) = setup();
+    // The OTP waveform and Fast drive unchanged pixels like changed ones.
+    for mode in [None, Some(DisplayMode::Fast)] {
+        if let Some(mode) = mode {
+            block_on(epd.set_mode(mode)).unwrap();
+        }
+        assert!(matches!(
+            block_on(epd.set_differential(true)),
+            Err(EpdDriverError::NotDifferential)
+        ));
+    }
+    block_on(epd.set_mode(DisplayMode::Terminal)).unwrap();
+    block_on(epd.set_differential(true)).unwrap();
+    assert!(matches!(
+        block_on(epd.set_mode(DisplayMode::Official)),
+        Err(EpdDriverError::NotDifferential)
+    ));
+    assert!(matches!(
+        block_on(epd.set_lut(&DisplayMode::Fast.lut_set())),
+        Err(EpdDriverError::NotDifferential)
+    ));
+    sim.feed(&hw.take_trace());
+
+    // `clear` sends an all-black old plane, so every pixel takes the full
+    // swing to white, then leaves old = new = white for the next frame.
+    block_on(epd.clear()).unwrap();
+    let ops = hw.take_trace();
+    assert!(ops.contains(&Op::Cmd(
+        Command::DataStartTransmission1,
+        vec![0xFF; BUF_SIZE]
+    )));
+    sim.feed(&ops);
+    assert_eq!(sim.last_driven(), WIDTH * HEIGHT);
+    assert!(sim.old_plane().iter().all(|&b| b == 0));
+    assert!(sim.screen().data.iter().all(|&b| b == 0));
+}
+
+#[test]
+fn display_diff_sends_old_plane() {
+    let (hw, mut epd, mut sim) = setup();
+    let old = vec![0xF0u8; BUF_SIZE];
+    let new = vec![0xF1u8; BUF_SIZE];
+    block_on(epd.display_diff(&old, &new)).unwrap();
+    sim.feed(&hw.take_trace());
+
+    assert_eq!(sim.old_plane(), &old[..]);
+    assert_eq!(sim.last_driven(), BUF_SIZE);
+    assert_eq!(sim.screen().data, new);
+}
+
+#[test]
+fn tricolor_planes() {
+    use embedded_graphics::prelude::*;
+    use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+    use pico_epd_driver::epd_driver::Epd800x480Bwr;
+    use pico_epd_driver::framebuffer::{TriColor, TriColorFramebuffer};
+
+    let hw = MockHw::new();
+    let mut epd = Epd800x480Bwr::new(hw.bus(), hw.led());
+    block_on(epd.init()).unwrap();
+
+    let (mut black, mut red) = (vec![0u8; BUF_SIZE], vec![0u8; BUF_SIZE]);
+    let mut fb = TriColorFramebuffer::new(&mut black, &mut red);
+    for (x, c) in [(0, TriColor::Black), (16, TriColor::Red)] {
+        Rectangle::new(Point::new(x, 0), Size::new(8, 2))
+            .into_styled(PrimitiveStyle::with_fill(c))
+            .draw(&mut fb)
+            .unwrap();
+    }
+    block_on(epd.display(fb.black(), fb.red())).unwrap();
+
+    let mut sim = PanelSim::new();
+    sim.feed(&hw.take_trace());
+    assert_eq!(sim.panel_setting(), 0x0F);
+    let (screen, shown_red) = (sim.screen(), sim.red());
+    assert!(screen.pixel(3, 1) && !shown_red.pixel(3, 1));
+    assert!(!screen.pixel(18, 0) && shown_red.pixel(18, 0));
+    assert!(!screen.pixel(10, 0) && !shown_red.pixel(10, 0));
+    assert_eq!(&shown_red.data[..], fb.red());
+}
+
+#[test]
+fn tricolor_on_another_panel() {
+    use embedded_graphics::prelude::*;
+    use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+    use pico_epd_driver::epd_driver::{EpdBwr, PanelSpec, Uc8179BwrPanel};
+    use pico_epd_driver::framebuffer::{TriColor, TriColorFramebuffer};
+    use pico_epd_driver::orientation::{Orientation, Rotation};
+
+    struct Bwr648;
+    impl PanelSpec for Bwr648 {
+        const WIDTH: usize = 648;
+        const HEIGHT: usize = 480;
+    }
+    impl Uc8179BwrPanel for Bwr648 {
+        const KWR_POWER_SEQUENCE: &'static [(Command, &'static [u8])] =
+            &[(Command::PowerSetting, &[0x07, 0x07, 0x3f, 0x3f])];
+        const KWR_PANEL_SETTING: u8 = 0x0F;
+        const KWR_PANEL_SEQUENCE: &'static [(Command, &'static [u8])] =
+            &[(Command::VcomAndDataInterval, &[0x11, 0x07])];
+    }
+
+    let hw = MockHw::new();
+    let mut epd: EpdBwr<Bwr648, _, _, _, _, _, _> = EpdBwr::new(hw.bus(), hw.led());
+    block_on(epd.init()).unwrap();
+    // The panel's own sequence is sent, not the 7.5" B V2 one.
+    let ops = hw.take_trace();
+    assert!(ops.contains(&Op::Cmd(
+        Command::PowerSetting,
+        vec![0x07, 0x07, 0x3f, 0x3f]
+    )));
+    assert!(
+        !ops.iter()
+            .any(|op| matches!(op, Op::Cmd(Command::Btst | Command::TconSetting, _)))
+    );
+    assert!(ops.contains(&Op::Cmd(Command::TRes, vec![0x02, 0x88, 0x01, 0xE0])));
+    let mut sim = PanelSim::for_panel::<Bwr648>();
+    sim.feed(&ops);
+
+    let len = Bwr648::BUF_SIZE;
+    let (mut black, mut red) = (vec![0u8; len], vec![0u8; len]);
+    let mut fb = TriColorFramebuffer::<Bwr648>::for_panel(&mut black, &mut red);
+    fb.set_orientation(Orientation::new(Rotation::Deg90));
+    assert_eq!(fb.size(), Size::new(480, 648));
+    // The top left of the portrait screen is the top right of the panel.
+    Rectangle::new(Point::new(0, 0), Size::new(8, 2))
+        .into_styled(PrimitiveStyle::with_fill(TriColor::Red))
+        .draw(&mut fb)
+        .unwrap();
+    block_on(epd.display(fb.black(), fb.red())).unwrap();
+    assert!(matches!(
+        block_on(epd.display(&fb.black()[1..], fb.red())),
+        Err(EpdDriverError::BadBufferLen { expected, got }) if expected == len && got == len - 1
+    ));
+
+    sim.feed(&hw.take_trace());
+    let (screen, shown_red) = (sim.screen(), sim.red());
+    assert!(shown_red.pixel(647, 0) && shown_red.pixel(646, 7));
+    assert!(!shown_red.pixel(645, 0) && !screen.pixel(647, 0));
+    assert_eq!(&shown_red.data[..], fb.red());
+}
+
+#[test]
+fn other_panel_geometry() {
+    use embedded_graphics::pixelcolor::BinaryColor;
+    use embedded_graphics::prelude::*;
+    use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+    use pico_epd_driver::epd_driver::{Epd as Driver, Panel5in83V2, PanelSpec};
+    use pico_epd_driver::framebuffer::{Flush, Framebuffer};
+
+    let hw = MockHw::new();
+    let mut epd: Driver<Panel5in83V2, _, _, _, _, _, _> = Driver::new(hw.bus(), hw.led());
+    block_on(epd.init()).unwrap();
+    let ops = hw.take_trace();
+    assert!(ops.contains(&Op::Cmd(Command::TRes, vec![0x02, 0x88, 0x01, 0xE0])));
+    assert!(
+        ops.iter()
+            .all(|o| o.as_cmd().is_none_or(|(c, _)| c != Command::Pll))
+    );
+
+    let mut sim = PanelSim::for_panel::<Panel5in83V2>();
+    sim.feed(&ops);
+    let mut buf = vec![0u8; Panel5in83V2::BUF_SIZE];
+    let mut fb = Framebuffer::for_panel(&mut buf);
+    fb.mark_all_dirty();
+    assert_eq!(block_on(fb.flush(&mut epd)).unwrap(), Flush::Full);
+    Rectangle::new(Point::new(640, 470), Size::new(8, 10))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(&mut fb)
+        .unwrap();
+    assert_eq!(block_on(fb.flush(&mut epd)).unwrap(), Flush::Partial(1));
+    sim.feed(&hw.take_trace());
+
+    let screen = sim.screen();
+    assert_eq!((screen.width, screen.height), (648, 480));
+    assert!(screen.pixel(647, 479) && !screen.pixel(639, 479));
+}
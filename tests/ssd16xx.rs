@@ -0,0 +1,147 @@
+//! SSD16xx backend transcripts, and the console/framebuffer code running on it.
+
+use embassy_futures::block_on;
+use pico_epd_driver::console::EpdConsole;
+use pico_epd_driver::epd_driver::mock::{MockBusy, MockHw, MockPin, MockSpi, Op};
+use pico_epd_driver::epd_driver::{
+    Panel4in2V2, Panel7in5Hd, PanelSpec, Rect, Ssd16xx, Ssd16xxPanel, SsdCommand,
+};
+
+type Ssd<P> = Ssd16xx<P, MockSpi, MockPin, MockPin, MockPin, MockBusy, MockPin>;
+
+fn setup<P: Ssd16xxPanel>() -> (MockHw, Ssd<P>) {
+    let hw = MockHw::new();
+    let epd = Ssd16xx::new(hw.bus(), hw.led());
+    (hw, epd)
+}
+
+/// Commands of a trace as raw bytes; BUSY waits show up as `None`.
+fn raw(ops: &[Op]) -> Vec<Option<(u8, Vec<u8>)>> {
+    ops.iter()
+        .map(|o| o.raw().map(|(c, d)| (c, d.to_vec())))
+        .collect()
+}
+
+fn cmd(c: SsdCommand, d: &[u8]) -> Option<(u8, Vec<u8>)> {
+    Some((c.into(), d.to_vec()))
+}
+
+#[test]
+fn init_transcript() {
+    let (hw, mut epd) = setup::<Panel4in2V2>();
+    block_on(epd.init()).unwrap();
+
+    let want = vec![
+        None, // reset pulse
+        None,
+        cmd(SsdCommand::SwReset, &[]),
+        None,
+        cmd(SsdCommand::DisplayUpdateControl1, &[0x00, 0x00]),
+        cmd(SsdCommand::BorderWaveform, &[0x05]),
+        cmd(SsdCommand::DataEntryMode, &[0x03]),
+        cmd(SsdCommand::RamXRange, &[0, 49]),
+        cmd(SsdCommand::RamYRange, &[0, 0, 0x2B, 0x01]),
+        cmd(SsdCommand::RamXCounter, &[0]),
+        cmd(SsdCommand::RamYCounter, &[0, 0]),
+    ];
+    let ops = hw.trace();
+    assert_eq!(ops[0], Op::Reset);
+    assert_eq!(ops[1], Op::WaitBusy);
+    assert_eq!(raw(&ops), want);
+    assert!(!hw.led_is_on());
+}
+
+#[test]
+fn display_writes_both_rams_inverted() {
+    let (hw, mut epd) = setup::<Panel4in2V2>();
+    let mut buf = vec![0u8; Panel4in2V2::BUF_SIZE];
+    buf[0] = 0x81;
+    block_on(epd.display(&buf)).unwrap();
+
+    let ops = raw(&hw.trace());
+    let rams: Vec<_> = ops
+        .iter()
+        .flatten()
+        .filter(|(c, _)| *c == 0x24 || *c == 0x26)
+        .collect();
+    assert_eq!(rams.len(), 2);
+    for (_, data) in rams {
+        assert_eq!(data.len(), Panel4in2V2::BUF_SIZE);
+        assert_eq!(data[0], 0x7E);
+        assert!(data[1..].iter().all(|&b| b == 0xFF));
+    }
+    assert_eq!(
+        ops[ops.len() - 3..],
+        [
+            cmd(SsdCommand::DisplayUpdateControl2, &[0xF7]),
+            cmd(SsdCommand::MasterActivation, &[]),
+            None,
+        ]
+    );
+
+    assert!(block_on(epd.display(&buf[1..])).is_err());
+}
+
+#[test]
+fn partial_window_in_pixels_on_ssd1677() {
+    let (hw, mut epd) = setup::<Panel7in5Hd>();
+    let r = Rect {
+        x: 296,
+        y: 300,
+        w: 16,
+        h: 2,
+    };
+    block_on(epd.display_partial(&[0xF0, 0x0F, 0x00, 0xFF], r)).unwrap();
+
+    let ops = raw(&hw.trace());
+    let window = [
+        cmd(SsdCommand::RamXRange, &[0x28, 0x01, 0x37, 0x01]),
+        cmd(SsdCommand::RamYRange, &[0x2C, 0x01, 0x2D, 0x01]),
+        cmd(SsdCommand::RamXCounter, &[0x28, 0x01]),
+        cmd(SsdCommand::RamYCounter, &[0x2C, 0x01]),
+    ];
+    let mut want = vec![None];
+    want.extend(window.clone());
+    want.extend([
+        cmd(SsdCommand::WriteRamBw, &[0x0F, 0xF0, 0xFF, 0x00]),
+        cmd(SsdCommand::DisplayUpdateControl2, &[0xFF]),
+        cmd(SsdCommand::MasterActivation, &[]),
+        None,
+    ]);
+    want.extend(window);
+    want.push(cmd(SsdCommand::WriteRamRed, &[0x0F, 0xF0, 0xFF, 0x00]));
+    assert_eq!(ops, want);
+}
+
+#[test]
+fn console_and_framebuffer_run_unchanged() {
+    use embedded_graphics::pixelcolor::BinaryColor;
+    use embedded_graphics::prelude::*;
+    use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+    use pico_epd_driver::framebuffer::{Flush, Framebuffer};
+
+    let (hw, mut epd) = setup::<Panel4in2V2>();
+    let mut buf = vec![0u8; Panel4in2V2::BUF_SIZE];
+    {
+        let mut console = EpdConsole::with_buffer(&mut epd, &mut buf);
+        block_on(console.show()).unwrap();
+        block_on(console.push("hello")).unwrap();
+        block_on(console.push("world")).unwrap();
+    }
+    let updates: Vec<u8> = raw(&hw.take_trace())
+        .into_iter()
+        .flatten()
+        .filter(|(c, _)| *c == u8::from(SsdCommand::DisplayUpdateControl2))
+        .map(|(_, d)| d[0])
+        .collect();
+    assert_eq!(updates, [0xF7, 0xF7, 0xFF]);
+
+    let mut fb = Framebuffer::for_panel(&mut buf);
+    Rectangle::new(Point::new(392, 290), Size::new(8, 10))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(&mut fb)
+        .unwrap();
+    assert_eq!(block_on(fb.flush(&mut epd)).unwrap(), Flush::Partial(1));
+    let ops = raw(&hw.take_trace());
+    assert!(ops.contains(&cmd(SsdCommand::RamXRange, &[49, 49])));
+}